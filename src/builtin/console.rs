@@ -0,0 +1,273 @@
+use super::deterministic;
+use crate::display::inspect;
+use crate::environment::{RcRealm, Realm};
+use js_sys::{Error, Reflect};
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use wasm_bindgen::prelude::*;
+
+/// Creates the factory of the consoles of cells. A console formats the arguments of `log`,
+/// `info`, `warn`, `error`, `debug`, `table`, `timeLog`, `timeEnd` and `group` like the
+/// console of DevTools and records them under its cell before passing them to the host's
+/// console. Its other members are the host's. Consoles created without a run attribute
+/// messages to the cell being evaluated when they are written.
+const CONSOLE: &str = r#"
+const LIMIT = 100;
+const consoles = new WeakSet();
+
+const text = (x) => typeof x === "string" ? x : preview(x);
+
+const number = (x, integer) => {
+  if (typeof x === "bigint") {
+    return x + "n";
+  }
+  if (typeof x === "symbol") {
+    return "NaN";
+  }
+  return String(integer ? Math.trunc(Number(x)) : Number(x));
+};
+
+// Applies the substitutions of a format string, e.g. `%s` or `%d`, and appends the other
+// arguments.
+const format = (args) => {
+  let i = 0;
+  const parts = [];
+  if (typeof args[0] === "string") {
+    i = 1;
+    parts.push(args[0].replace(/%([sdifoOc%])/g, (match, type) => {
+      if (type === "%") {
+        return "%";
+      }
+      if (i >= args.length) {
+        return match;
+      }
+      const value = args[i++];
+      switch (type) {
+        case "s": return text(value);
+        case "d": case "i": return number(value, true);
+        case "f": return number(value, false);
+        case "c": return "";
+        default: return preview(value);
+      }
+    }));
+  }
+  return parts.concat(args.slice(i).map(text)).join(" ");
+};
+
+const align = (header, rows) => {
+  const widths = header.map((_, i) => Math.max(...[header, ...rows].map((row) => row[i].length)));
+  const line = (row) => row.map((x, i) => x.padEnd(widths[i])).join("  ").trimEnd();
+  return [line(header), widths.map((width) => "-".repeat(width)).join("  "), ...rows.map(line)].join("\n");
+};
+
+const table = (data, columns) => {
+  if (typeof data !== "object" || data === null) {
+    return format([data]);
+  }
+  const entries = data instanceof Map
+    ? [...data].map(([key, value]) => [preview(key), value])
+    : data instanceof Set
+      ? [...data].map((value, i) => [String(i), value])
+      : Object.entries(data);
+  const object = (x) => typeof x === "object" && x !== null;
+  const names = Array.isArray(columns)
+    ? columns.map(String)
+    : [...new Set(entries.flatMap(([_, value]) => object(value) ? Object.keys(value) : []))];
+  const values = entries.some(([_, value]) => !object(value));
+  const header = ["(index)", ...names, ...(values ? ["Value"] : [])];
+  const rows = entries.slice(0, LIMIT).map(([key, value]) => [
+    key,
+    ...names.map((name) => object(value) && name in value ? preview(value[name]) : ""),
+    ...(values ? [object(value) ? "" : preview(value)] : []),
+  ]);
+  const more = entries.length > LIMIT ? "\n… " + (entries.length - LIMIT) + " more rows" : "";
+  return align(header, rows) + more;
+};
+
+const create = (cell, run) => {
+  const timers = new Map();
+  let depth = 0;
+  const write = (level, message) => record(cell, run, level, message, depth);
+  const elapsed = (label) => label + ": " + Number((now() - timers.get(label)).toFixed(3)) + " ms";
+  const timer = (name, remove) => (label = "default", ...data) => {
+    label = String(label);
+    if (!timers.has(label)) {
+      write("warn", "Timer \"" + label + "\" does not exist");
+    } else {
+      write(name, elapsed(label) + (data.length ? " " + format(data) : ""));
+      if (remove) {
+        timers.delete(label);
+      }
+    }
+  };
+  const members = {
+    table: (data, columns) => write("table", table(data, columns)),
+    time: (label = "default") => {
+      label = String(label);
+      if (timers.has(label)) {
+        write("warn", "Timer \"" + label + "\" already exists");
+      } else {
+        timers.set(label, now());
+      }
+    },
+    timeLog: timer("timeLog", false),
+    timeEnd: timer("timeEnd", true),
+    group: (...label) => {
+      write("group", label.length ? format(label) : "console.group");
+      depth++;
+    },
+    groupEnd: () => {
+      depth = Math.max(depth - 1, 0);
+    },
+  };
+  members.groupCollapsed = members.group;
+  for (const level of ["log", "info", "warn", "error", "debug"]) {
+    members[level] = (...args) => write(level, format(args));
+  }
+  for (const [name, member] of Object.entries(members)) {
+    members[name] = (...args) => {
+      member(...args);
+      if (typeof console[name] === "function") {
+        console[name](...args);
+      }
+    };
+  }
+  const proxy = new Proxy(console, {
+    get: (target, key) => {
+      if (Object.prototype.hasOwnProperty.call(members, key)) {
+        return members[key];
+      }
+      const value = Reflect.get(target, key);
+      return typeof value === "function" ? value.bind(target) : value;
+    },
+  });
+  consoles.add(proxy);
+  return proxy;
+};
+
+return { create, owns: (x) => consoles.has(x) };
+"#;
+
+/// A message a cell wrote to the console.
+#[derive(Clone, Debug)]
+pub struct ConsoleEntry {
+    /// Id of the cell the message is attributed to, `None` for cells without id.
+    pub cell: Option<String>,
+    /// Method the message was written with, e.g. `log`, `error` or `table`.
+    pub level: String,
+    pub text: String,
+    /// Number of groups the message is nested in.
+    pub depth: u32,
+}
+
+impl ConsoleEntry {
+    pub fn to_js(&self) -> Result<JsValue, Error> {
+        let entry = js_sys::Object::new();
+        let entries = [
+            (
+                "cell",
+                self.cell
+                    .as_deref()
+                    .map(JsValue::from_str)
+                    .unwrap_or(JsValue::null()),
+            ),
+            ("level", JsValue::from_str(&self.level)),
+            ("text", JsValue::from_str(&self.text)),
+            ("depth", JsValue::from(self.depth)),
+        ];
+        for (key, value) in entries {
+            Reflect::set(&entry, &JsValue::from_str(key), &value)?;
+        }
+        Ok(JsValue::from(entry))
+    }
+}
+
+/// Returns a `console` attributing messages to the cell `cell` in the run `run`, or to the
+/// cell being evaluated when they are written if `run` is `None`.
+pub(crate) fn console_object(realm: &RcRealm, cell: Option<&str>, run: Option<u32>) -> JsValue {
+    let create = Reflect::get(&factory(realm), &JsValue::from_str("create")).unwrap();
+    let cell = cell.map(JsValue::from_str).unwrap_or(JsValue::null());
+    let run = run.map(JsValue::from).unwrap_or(JsValue::undefined());
+    js_sys::Function::from(create)
+        .call2(&JsValue::undefined(), &cell, &run)
+        .unwrap()
+}
+
+/// Returns whether `value` is a `console` created by `console_object`.
+pub(crate) fn is_console_object(realm: &RcRealm, value: &JsValue) -> bool {
+    let owns = Reflect::get(&factory(realm), &JsValue::from_str("owns")).unwrap();
+    js_sys::Function::from(owns)
+        .call1(&JsValue::undefined(), value)
+        .map(|x| x.is_truthy())
+        .unwrap_or(false)
+}
+
+/// Returns the factory of the consoles of a realm, creating it on first use.
+fn factory(realm: &RcRealm) -> js_sys::Object {
+    let factory = realm.borrow().console.clone();
+    factory.unwrap_or_else(|| {
+        let factory = js_sys::Function::new_with_args("record, preview, now", CONSOLE)
+            .call3(
+                &JsValue::undefined(),
+                &record(Rc::downgrade(realm)),
+                &Reflect::get(&inspect::inspector(realm), &JsValue::from_str("preview")).unwrap(),
+                &now(Rc::downgrade(realm)),
+            )
+            .map(js_sys::Object::from)
+            .unwrap();
+        realm.borrow_mut().console = Some(factory.clone());
+        factory
+    })
+}
+
+/// Records a message in the result of its cell if it belongs to the current run, and passes
+/// it to the host's console handler.
+fn record(realm: Weak<RefCell<Realm>>) -> js_sys::Function {
+    Closure::wrap(Box::new(
+        move |cell: Option<String>, run: Option<u32>, level: String, text: String, depth: u32| {
+            let realm = match realm.upgrade() {
+                Some(realm) => realm,
+                None => return,
+            };
+            let (entry, handler) = {
+                let mut realm = match realm.try_borrow_mut() {
+                    Ok(realm) => realm,
+                    Err(_) => return,
+                };
+                let entry = ConsoleEntry {
+                    cell: if run.is_some() {
+                        cell
+                    } else {
+                        realm.cell.clone()
+                    },
+                    level,
+                    text,
+                    depth,
+                };
+                if run.unwrap_or(realm.console_run) == realm.console_run {
+                    if let Some(output) = realm.console_output.as_mut() {
+                        output.push(entry.clone());
+                    }
+                }
+                (entry, realm.config.console_handler.clone())
+            };
+            if let (Some(handler), Ok(entry)) = (handler, entry.to_js()) {
+                // Errors of the host's handler must not fail the cell that logged.
+                let _ = handler.call1(&JsValue::undefined(), &entry);
+            }
+        },
+    )
+        as Box<dyn FnMut(Option<String>, Option<u32>, String, String, u32)>)
+    .into_js_value()
+    .unchecked_into()
+}
+
+/// Returns the time consoles measure with, the virtual clock in deterministic mode.
+fn now(realm: Weak<RefCell<Realm>>) -> js_sys::Function {
+    Closure::wrap(Box::new(move || match realm.upgrade() {
+        Some(realm) if realm.try_borrow().is_ok() => deterministic::now(&realm),
+        _ => instant::now(),
+    }) as Box<dyn FnMut() -> f64>)
+    .into_js_value()
+    .unchecked_into()
+}
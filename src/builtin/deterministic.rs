@@ -0,0 +1,233 @@
+use crate::environment::RcRealm;
+use js_sys::{Error, Reflect};
+use wasm_bindgen::prelude::*;
+
+/// Globals replaced in deterministic mode, if the capability policy exposes them.
+/// `VIRTUAL_GLOBALS` are replaced in any case.
+pub(crate) const DETERMINISTIC_GLOBALS: &[&str] = &[
+    "Math",
+    "crypto",
+    "Date",
+    "performance",
+    "setTimeout",
+    "clearTimeout",
+    "setInterval",
+    "clearInterval",
+];
+
+/// Sources of randomness and time cells get in deterministic mode even if the capability policy
+/// hides them, with only their deterministic members, so that they never reach the host's.
+pub(crate) const VIRTUAL_GLOBALS: &[&str] = &["crypto", "performance"];
+
+/// Creates the deterministic globals. Random numbers come from an sfc32 generator seeded with
+/// the hash of the seed. The virtual clock starts at `startTime` and only advances when a timer
+/// is due: timers run one per task in the order of their virtual due time, without waiting.
+const DETERMINISTIC: &str = r#"
+const hash = (text) => {
+  let h = 1779033703 ^ text.length;
+  for (let i = 0; i < text.length; i++) {
+    h = Math.imul(h ^ text.charCodeAt(i), 3432918353);
+    h = (h << 13) | (h >>> 19);
+  }
+  return () => {
+    h = Math.imul(h ^ (h >>> 16), 2246822507);
+    h = Math.imul(h ^ (h >>> 13), 3266489909);
+    return (h ^= h >>> 16) >>> 0;
+  };
+};
+const seeds = hash(seed);
+let a = seeds(), b = seeds(), c = seeds(), d = seeds();
+const next = () => {
+  a >>>= 0; b >>>= 0; c >>>= 0; d >>>= 0;
+  let t = (a + b) | 0;
+  a = b ^ (b >>> 9);
+  b = (c + (c << 3)) | 0;
+  c = (c << 21) | (c >>> 11);
+  d = (d + 1) | 0;
+  t = (t + d) | 0;
+  c = (c + t) | 0;
+  return t >>> 0;
+};
+const random = () => next() / 4294967296;
+
+const getRandomValues = (array) => {
+  if (!ArrayBuffer.isView(array) || array instanceof Float32Array || array instanceof Float64Array
+    || array instanceof DataView) {
+    throw new TypeError("TypeError: getRandomValues() expects an integer typed array.");
+  }
+  if (array.byteLength > 65536) {
+    throw new RangeError("RangeError: getRandomValues() can fill at most 65536 bytes.");
+  }
+  const bytes = new Uint8Array(array.buffer, array.byteOffset, array.byteLength);
+  for (let i = 0; i < bytes.length; i++) {
+    bytes[i] = next() & 255;
+  }
+  return array;
+};
+const randomUUID = () => {
+  const bytes = getRandomValues(new Uint8Array(16));
+  bytes[6] = (bytes[6] & 15) | 64;
+  bytes[8] = (bytes[8] & 63) | 128;
+  const hex = [...bytes].map((x) => x.toString(16).padStart(2, "0")).join("");
+  return hex.slice(0, 8) + "-" + hex.slice(8, 12) + "-" + hex.slice(12, 16) + "-"
+    + hex.slice(16, 20) + "-" + hex.slice(20);
+};
+
+let elapsed = 0;
+const now = () => elapsed;
+const clock = () => startTime + elapsed;
+
+const RealDate = Date;
+const VirtualDate = new Proxy(RealDate, {
+  construct: (target, args, newTarget) =>
+    Reflect.construct(target, args.length ? args : [clock()], newTarget),
+  apply: () => new RealDate(clock()).toString(),
+  get: (target, key, receiver) => key === "now" ? clock : Reflect.get(target, key, receiver),
+});
+
+const wrap = (target, members) => new Proxy(target, {
+  get: (target, key) => {
+    if (Object.prototype.hasOwnProperty.call(members, key)) {
+      return members[key];
+    }
+    const value = Reflect.get(target, key);
+    return typeof value === "function" ? value.bind(target) : value;
+  },
+});
+
+const realSetTimeout = setTimeout;
+const timers = new Map();
+let ids = 0;
+let sequence = 0;
+let scheduled = false;
+const schedule = () => {
+  if (!scheduled && timers.size) {
+    scheduled = true;
+    realSetTimeout(run, 0);
+  }
+};
+const run = () => {
+  scheduled = false;
+  let timer = null;
+  for (const x of timers.values()) {
+    if (!timer || x.due < timer.due || (x.due === timer.due && x.sequence < timer.sequence)) {
+      timer = x;
+    }
+  }
+  if (!timer) {
+    return;
+  }
+  elapsed = Math.max(elapsed, timer.due);
+  if (timer.interval === null) {
+    timers.delete(timer.id);
+  } else {
+    timer.due = elapsed + timer.interval;
+    timer.sequence = sequence++;
+  }
+  schedule();
+  timer.callback(...timer.args);
+};
+const addTimer = (callback, delay, args, repeat) => {
+  if (typeof callback !== "function") {
+    throw new TypeError("TypeError: Timers expect a function in deterministic mode.");
+  }
+  delay = Math.max(0, Number(delay) || 0);
+  const id = ++ids;
+  timers.set(id, {
+    id, callback, args, sequence: sequence++, due: elapsed + delay,
+    interval: repeat ? Math.max(1, delay) : null,
+  });
+  schedule();
+  return id;
+};
+
+const virtualCrypto = { getRandomValues, randomUUID };
+const virtualPerformance = { now, timeOrigin: startTime };
+
+return {
+  now,
+  Math: wrap(Math, { random }),
+  crypto: wrap(typeof crypto === "undefined" ? {} : crypto, virtualCrypto),
+  Date: VirtualDate,
+  performance: wrap(typeof performance === "undefined" ? {} : performance, virtualPerformance),
+  virtual: { crypto: virtualCrypto, performance: virtualPerformance },
+  setTimeout: (callback, delay, ...args) => addTimer(callback, delay, args, false),
+  setInterval: (callback, delay, ...args) => addTimer(callback, delay, args, true),
+  clearTimeout: (id) => { timers.delete(id); },
+  clearInterval: (id) => { timers.delete(id); },
+};
+"#;
+
+/// Seed and start time of the deterministic mode, which the host records with the notebook
+/// to reproduce its outputs.
+#[derive(Clone, Debug)]
+pub struct DeterministicMode {
+    pub seed: String,
+    /// Time of the virtual clock when the environments are created, in milliseconds since
+    /// the Unix epoch.
+    pub start_time: f64,
+}
+
+impl DeterministicMode {
+    /// Creates the mode from the host's options, an object with `seed`, a number or string,
+    /// and `startTime`, a number of milliseconds, a date string or a `Date`.
+    pub fn from_js(options: &JsValue) -> Result<DeterministicMode, Error> {
+        let seed = Reflect::get(options, &JsValue::from_str("seed"))?;
+        let seed =
+            match (seed.as_string(), seed.as_f64()) {
+                (Some(seed), _) => seed,
+                (None, Some(seed)) => seed.to_string(),
+                _ => return Err(Error::from(js_sys::TypeError::new(
+                    "TypeError: The seed of the deterministic mode must be a number or a string.",
+                ))),
+            };
+        let start_time = Reflect::get(options, &JsValue::from_str("startTime"))?;
+        let start_time = match start_time.as_f64() {
+            Some(start_time) => start_time,
+            None if start_time.is_undefined() => 0.0,
+            None => js_sys::Date::new(&start_time).get_time(),
+        };
+        if !start_time.is_finite() {
+            return Err(Error::from(js_sys::TypeError::new(
+                "TypeError: The start time of the deterministic mode is not a valid date.",
+            )));
+        }
+        Ok(DeterministicMode { seed, start_time })
+    }
+}
+
+/// Returns the deterministic globals of a realm if the deterministic mode is enabled, creating
+/// them on first use.
+pub(crate) fn globals(realm: &RcRealm) -> Option<js_sys::Object> {
+    let (globals, mode) = {
+        let realm = realm.borrow();
+        (
+            realm.deterministic.clone(),
+            realm.config.deterministic.clone(),
+        )
+    };
+    let mode = mode?;
+    globals.or_else(|| {
+        let globals = js_sys::Function::new_with_args("seed, startTime", DETERMINISTIC)
+            .call2(
+                &JsValue::undefined(),
+                &JsValue::from_str(&mode.seed),
+                &JsValue::from_f64(mode.start_time),
+            )
+            .map(js_sys::Object::from)
+            .ok()?;
+        realm.borrow_mut().deterministic = Some(globals.clone());
+        Some(globals)
+    })
+}
+
+/// Returns the time in milliseconds for measuring durations, from the virtual clock in
+/// deterministic mode.
+pub(crate) fn now(realm: &RcRealm) -> f64 {
+    let globals = realm.borrow().deterministic.clone();
+    globals
+        .and_then(|x| Reflect::get(&x, &JsValue::from_str("now")).ok())
+        .and_then(|x| js_sys::Function::from(x).call0(&JsValue::undefined()).ok())
+        .and_then(|x| x.as_f64())
+        .unwrap_or_else(instant::now)
+}
@@ -0,0 +1,161 @@
+use super::html;
+use crate::environment::{RcRealm, Realm};
+use crate::value::Value;
+use js_sys::{Error, Reflect};
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+/// Returns a `display` builtin attributing outputs to the cell `cell`, or to the cell being
+/// evaluated when it is called if `cell` is `None`. `display(value, { id })` shows a value as an
+/// additional output and returns the id of the output, `display.update(id, value)` replaces the
+/// output with the id, e.g. of a progress bar. The builtin without a cell is created once per
+/// realm, and all builtins share its `update`.
+pub(crate) fn display_function(realm: &RcRealm, cell: Option<&str>) -> js_sys::Function {
+    let display = realm.borrow().display.clone();
+    let unbound = display.unwrap_or_else(|| {
+        let display = create(realm, None);
+        let weak = Rc::downgrade(realm);
+        let update = Closure::wrap(Box::new(move |id: JsValue, value: JsValue| {
+            show(&weak, value, Some(id_string(&id)?), true, None)?;
+            Ok(())
+        })
+            as Box<dyn FnMut(JsValue, JsValue) -> Result<(), Error>>)
+        .into_js_value();
+        Reflect::set(&display, &JsValue::from_str("update"), &update).unwrap();
+        realm.borrow_mut().display = Some(display.clone());
+        display
+    });
+    match cell {
+        Some(cell) => {
+            let display = create(realm, Some(String::from(cell)));
+            let update = Reflect::get(&unbound, &JsValue::from_str("update")).unwrap();
+            Reflect::set(&display, &JsValue::from_str("update"), &update).unwrap();
+            display
+        }
+        None => unbound,
+    }
+}
+
+/// Returns whether `value` is a `display` created by `display_function`.
+pub(crate) fn is_display_function(realm: &RcRealm, value: &JsValue) -> bool {
+    let update = |x: &JsValue| Reflect::get(x, &JsValue::from_str("update")).ok();
+    value.is_function() && update(value) == update(&JsValue::from(display_function(realm, None)))
+}
+
+fn create(realm: &RcRealm, cell: Option<String>) -> js_sys::Function {
+    let weak = Rc::downgrade(realm);
+    Closure::wrap(Box::new(move |value: JsValue, options: JsValue| {
+        let id = if options.is_null() || options.is_undefined() {
+            JsValue::undefined()
+        } else {
+            Reflect::get(&options, &JsValue::from_str("id"))?
+        };
+        let id = if id.is_undefined() {
+            None
+        } else {
+            Some(id_string(&id)?)
+        };
+        Ok(JsValue::from_str(&show(
+            &weak,
+            value,
+            id,
+            false,
+            cell.clone(),
+        )?))
+    })
+        as Box<dyn FnMut(JsValue, JsValue) -> Result<JsValue, Error>>)
+    .into_js_value()
+    .unchecked_into()
+}
+
+fn id_string(id: &JsValue) -> Result<String, Error> {
+    id.as_string().ok_or_else(|| {
+        Error::from(js_sys::TypeError::new(
+            "TypeError: The id of a display must be a string.",
+        ))
+    })
+}
+
+/// Renders a value and passes it to the host's display handler after the outputs displayed
+/// before, so that updates arrive in order. New outputs are attributed to `cell`, or to the cell
+/// being evaluated if it is `None`, and updates to the cell that displayed the output first.
+fn show(
+    realm: &Weak<RefCell<Realm>>,
+    value: JsValue,
+    id: Option<String>,
+    update: bool,
+    cell: Option<String>,
+) -> Result<String, Error> {
+    let realm = realm
+        .upgrade()
+        .ok_or_else(|| Error::new("ERROR: The interpreter of this display has been reset."))?;
+    let (id, cell) = {
+        let mut realm = realm.borrow_mut();
+        let id = id.unwrap_or_else(|| {
+            realm.display_count += 1;
+            format!("display-{}", realm.display_count)
+        });
+        let cell = if update {
+            realm.displays.get(&id).cloned().ok_or_else(|| {
+                Error::from(js_sys::ReferenceError::new(&format!(
+                    "ReferenceError: There is no display with the id {:?}.",
+                    id
+                )))
+            })?
+        } else {
+            let cell = cell.or_else(|| realm.cell.clone());
+            realm.displays.insert(id.clone(), cell.clone());
+            cell
+        };
+        (id, cell)
+    };
+    let value = Value::from(value);
+    let output = html::sanitize(&realm, value.output()?)?;
+    // The bundle is awaited from now on, so that failed renderings are handled while the
+    // outputs before are rendered.
+    let bundle = JsFuture::from(crate::display::render(&value, &output, &realm)?);
+    let previous = realm.borrow_mut().display_queue.take();
+    let weak = Rc::downgrade(&realm);
+    let kind = if update { "update" } else { "display" };
+    let message = message(kind, cell.as_deref(), &id)?;
+    let shown = future_to_promise(async move {
+        if let Some(previous) = previous {
+            let _ = JsFuture::from(previous).await;
+        }
+        let (data, error) = match bundle.await {
+            Ok(data) => (data, JsValue::null()),
+            Err(err) => (JsValue::null(), JsValue::from(Error::from(err).message())),
+        };
+        Reflect::set(&message, &JsValue::from_str("data"), &data)?;
+        Reflect::set(&message, &JsValue::from_str("error"), &error)?;
+        let handler = weak
+            .upgrade()
+            .and_then(|x| x.borrow().config.display_handler.clone());
+        if let Some(handler) = handler {
+            // Errors of the host's handler must not fail the cell that displayed the value.
+            let _ = handler.call1(&JsValue::undefined(), &message);
+        }
+        Ok(JsValue::undefined())
+    });
+    realm.borrow_mut().display_queue = Some(shown);
+    Ok(id)
+}
+
+/// Creates the message the host's display handler is called with, without its data.
+fn message(kind: &str, cell: Option<&str>, id: &str) -> Result<js_sys::Object, Error> {
+    let message = js_sys::Object::new();
+    let entries = [
+        ("type", JsValue::from_str(kind)),
+        (
+            "cell",
+            cell.map(JsValue::from_str).unwrap_or(JsValue::null()),
+        ),
+        ("id", JsValue::from_str(id)),
+    ];
+    for (key, value) in entries {
+        Reflect::set(&message, &JsValue::from_str(key), &value)?;
+    }
+    Ok(message)
+}
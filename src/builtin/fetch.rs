@@ -0,0 +1,445 @@
+use super::deterministic;
+use crate::environment::{RcRealm, Realm};
+use js_sys::{Error, Reflect};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::rc::{Rc, Weak};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+/// Responses with larger bodies are rejected unless the host sets another limit.
+const DEFAULT_MAX_BYTES: f64 = 50.0 * 1024.0 * 1024.0;
+
+/// Redirects followed before a request fails, as with the `fetch` of browsers.
+const MAX_REDIRECTS: usize = 20;
+
+/// Restrictions of the `fetch` available to cells.
+#[derive(Clone, Debug)]
+pub struct FetchPolicy {
+    /// Origins cells can fetch from, e.g. `https://api.example.com`. `None` allows all origins.
+    /// If set, redirects are followed one by one and each target is checked before it is
+    /// requested. Redirects whose target is hidden, as with cross-origin requests in browsers,
+    /// are rejected.
+    pub origins: Option<BTreeSet<String>>,
+    /// Whether requests send cookies and other credentials, which are omitted otherwise.
+    pub credentials: bool,
+    /// Maximum size of a response body in bytes.
+    pub max_bytes: f64,
+}
+
+impl Default for FetchPolicy {
+    fn default() -> FetchPolicy {
+        FetchPolicy {
+            origins: None,
+            credentials: false,
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+impl FetchPolicy {
+    /// Creates a policy from the host's options, an object with the optional properties
+    /// `origins`, an array of allowed origins, `credentials` and `maxBytes`.
+    pub fn from_js(options: &JsValue) -> Result<FetchPolicy, Error> {
+        if !options.is_object() {
+            return Err(policy_error("The fetch policy must be an object."));
+        }
+        let mut policy = FetchPolicy::default();
+        let origins = Reflect::get(options, &JsValue::from_str("origins"))?;
+        if js_sys::Array::is_array(&origins) {
+            policy.origins = Some(
+                js_sys::Array::from(&origins)
+                    .iter()
+                    .map(|x| {
+                        x.as_string()
+                            .and_then(|x| web_sys::Url::new(&x).ok())
+                            .map(|x| x.origin())
+                            .ok_or_else(|| policy_error(&format!("{:?} is not a valid origin.", x)))
+                    })
+                    .collect::<Result<_, Error>>()?,
+            );
+        } else if !origins.is_undefined() {
+            return Err(policy_error("\"origins\" must be an array."));
+        }
+        policy.credentials = Reflect::get(options, &JsValue::from_str("credentials"))?.is_truthy();
+        let max_bytes = Reflect::get(options, &JsValue::from_str("maxBytes"))?;
+        match max_bytes.as_f64() {
+            Some(max_bytes) if max_bytes >= 0.0 => policy.max_bytes = max_bytes,
+            None if max_bytes.is_undefined() => (),
+            _ => return Err(policy_error("\"maxBytes\" must be a positive number.")),
+        }
+        Ok(policy)
+    }
+
+    /// Returns whether cells can fetch `url`.
+    pub fn allows(&self, url: &str) -> bool {
+        match (&self.origins, web_sys::Url::new(url)) {
+            (None, _) => true,
+            (Some(origins), Ok(url)) => origins.contains(&url.origin()),
+            (Some(_), Err(_)) => false,
+        }
+    }
+}
+
+/// A request of a cell, which the host shows in its network log.
+#[derive(Clone, Debug)]
+pub struct RequestRecord {
+    pub url: String,
+    pub method: String,
+    /// HTTP status of the response, `None` if no response was received.
+    pub status: Option<u16>,
+    /// Size of the response body in bytes.
+    pub bytes: Option<f64>,
+    /// Time until the response body was read in milliseconds.
+    pub duration: f64,
+    /// Message of the error the request failed with, e.g. because its origin is blocked.
+    pub error: Option<String>,
+}
+
+impl RequestRecord {
+    pub fn to_js(&self) -> Result<JsValue, Error> {
+        let record = js_sys::Object::new();
+        let optional = |x: Option<f64>| x.map(JsValue::from_f64).unwrap_or(JsValue::null());
+        let entries = [
+            ("url", JsValue::from_str(&self.url)),
+            ("method", JsValue::from_str(&self.method)),
+            ("status", optional(self.status.map(f64::from))),
+            ("bytes", optional(self.bytes)),
+            ("duration", JsValue::from_f64(self.duration)),
+            (
+                "error",
+                self.error
+                    .as_deref()
+                    .map(JsValue::from_str)
+                    .unwrap_or(JsValue::null()),
+            ),
+        ];
+        for (key, value) in entries {
+            Reflect::set(&record, &JsValue::from_str(key), &value)?;
+        }
+        Ok(JsValue::from(record))
+    }
+}
+
+/// Returns the `fetch` of a realm, creating it on first use. It applies the realm's fetch
+/// policy and records each request under the cell being evaluated.
+pub(crate) fn fetch_function(realm: &RcRealm) -> js_sys::Function {
+    let fetch = realm.borrow().fetch.clone();
+    fetch.unwrap_or_else(|| {
+        let fetch = wrap_fetch(Rc::downgrade(realm));
+        realm.borrow_mut().fetch = Some(fetch.clone());
+        fetch
+    })
+}
+
+fn wrap_fetch(realm: Weak<RefCell<Realm>>) -> js_sys::Function {
+    Closure::wrap(Box::new(move |input: JsValue, init: JsValue| {
+        let realm = realm.clone();
+        // The cell is read when the request is made, responses may arrive after it finished.
+        let cell = realm.upgrade().and_then(|x| x.borrow().cell.clone());
+        wasm_bindgen_futures::future_to_promise(async move {
+            let realm = realm.upgrade().ok_or_else(|| {
+                Error::new("ERROR: The interpreter of this request has been reset.")
+            })?;
+            fetch(input, init, cell, &realm)
+                .await
+                .map_err(JsValue::from)
+        })
+    })
+        as Box<dyn FnMut(JsValue, JsValue) -> js_sys::Promise>)
+    .into_js_value()
+    .unchecked_into()
+}
+
+async fn fetch(
+    input: JsValue,
+    init: JsValue,
+    cell: Option<String>,
+    realm: &RcRealm,
+) -> Result<JsValue, Error> {
+    let start = deterministic::now(realm);
+    let (policy, fetch) = {
+        let realm = realm.borrow();
+        let fetch = realm.config.policy.wrapper("fetch").cloned();
+        (realm.config.fetch.clone(), fetch)
+    };
+    let global = js_sys::global();
+    let fetch = match fetch {
+        Some(fetch) => fetch,
+        None => Reflect::get(&global, &JsValue::from_str("fetch"))?,
+    }
+    .dyn_into::<js_sys::Function>()
+    .map_err(|_| Error::new("ERROR: fetch is not available."))?;
+    let request = construct("Request", &input, &init)?;
+    let url = Reflect::get(&request, &JsValue::from_str("url"))?
+        .as_string()
+        .unwrap_or_default();
+    let method = Reflect::get(&request, &JsValue::from_str("method"))?
+        .as_string()
+        .unwrap_or_default();
+    let mut status = None;
+    let result = async {
+        if !policy.allows(&url) {
+            return Err(Error::from(js_sys::TypeError::new(&format!(
+                "TypeError: Cells cannot fetch {:?}, its origin is not allowed.",
+                url
+            ))));
+        }
+        let init = js_sys::Object::new();
+        if !policy.credentials {
+            Reflect::set(
+                &init,
+                &JsValue::from_str("credentials"),
+                &JsValue::from_str("omit"),
+            )?;
+        }
+        if policy.origins.is_some() {
+            Reflect::set(
+                &init,
+                &JsValue::from_str("redirect"),
+                &JsValue::from_str("manual"),
+            )?;
+        }
+        let mut request = construct("Request", &request, &init)?;
+        let mut redirects = 0;
+        let response = loop {
+            let response = fetch.call1(&JsValue::undefined(), &clone_request(&request)?)?;
+            let response = JsFuture::from(js_sys::Promise::resolve(&response)).await?;
+            let response = web_sys::Response::from(response);
+            status = Some(response.status());
+            let location = match redirect_location(&request, &response)? {
+                Some(location) => location,
+                None => break response,
+            };
+            cancel_body(&response)?;
+            let location = match location {
+                Some(location) if policy.allows(&location) => location,
+                Some(location) => {
+                    return Err(Error::from(js_sys::TypeError::new(&format!(
+                        "TypeError: Cells cannot fetch {:?}, it redirects to {:?}, whose origin is not allowed.",
+                        url, location
+                    ))))
+                }
+                None => {
+                    return Err(Error::from(js_sys::TypeError::new(&format!(
+                        "TypeError: Cells cannot fetch {:?}, it redirects to a target that cannot be checked against the fetch policy.",
+                        url
+                    ))))
+                }
+            };
+            redirects += 1;
+            if redirects > MAX_REDIRECTS {
+                return Err(Error::from(js_sys::TypeError::new(&format!(
+                    "TypeError: Cells cannot fetch {:?}, it redirects more than {} times.",
+                    url, MAX_REDIRECTS
+                ))));
+            }
+            request = redirected_request(&request, &location, response.status())?;
+        };
+        limit_body(&url, response, policy.max_bytes).await
+    }
+    .await;
+    let (bytes, error) = match &result {
+        Ok((_, bytes)) => (Some(*bytes), None),
+        Err(err) => (None, Some(String::from(err.message()))),
+    };
+    let record = RequestRecord {
+        url,
+        method,
+        status,
+        bytes,
+        duration: deterministic::now(realm) - start,
+        error,
+    };
+    realm
+        .borrow_mut()
+        .requests
+        .entry(cell.unwrap_or_default())
+        .or_default()
+        .push(record);
+    result.map(|x| x.0)
+}
+
+/// Reads the body of a response and returns an equivalent response with its size. Bodies
+/// larger than `max_bytes` are rejected, by their announced length if possible, otherwise as
+/// soon as more bytes have been read.
+async fn limit_body(
+    url: &str,
+    response: web_sys::Response,
+    max_bytes: f64,
+) -> Result<(JsValue, f64), Error> {
+    let too_large = || {
+        Error::from(js_sys::RangeError::new(&format!(
+            "RangeError: The response of {:?} is larger than {} bytes.",
+            url, max_bytes
+        )))
+    };
+    let kind = Reflect::get(&response, &JsValue::from_str("type"))?.as_string();
+    if kind.as_deref().unwrap_or_default().starts_with("opaque") {
+        // Opaque responses have no readable body.
+        return Ok((JsValue::from(response), 0.0));
+    }
+    let headers = Reflect::get(&response, &JsValue::from_str("headers"))?;
+    let length = Reflect::get(&headers, &JsValue::from_str("get"))?
+        .dyn_into::<js_sys::Function>()?
+        .call1(&headers, &JsValue::from_str("content-length"))?
+        .as_string()
+        .and_then(|x| x.parse::<f64>().ok());
+    if length.is_some_and(|x| x > max_bytes) {
+        cancel_body(&response)?;
+        return Err(too_large());
+    }
+    let stream = Reflect::get(&response, &JsValue::from_str("body"))?;
+    let mut chunks = Vec::new();
+    let mut bytes = 0.0;
+    if !stream.is_null() {
+        let reader = method(&stream, "getReader")?.call0(&stream)?;
+        let read = method(&reader, "read")?;
+        loop {
+            let chunk = JsFuture::from(js_sys::Promise::from(read.call0(&reader)?)).await?;
+            if Reflect::get(&chunk, &JsValue::from_str("done"))?.is_truthy() {
+                break;
+            }
+            let chunk =
+                js_sys::Uint8Array::from(Reflect::get(&chunk, &JsValue::from_str("value"))?);
+            bytes += chunk.length() as f64;
+            if bytes > max_bytes {
+                method(&reader, "cancel")?.call0(&reader)?;
+                return Err(too_large());
+            }
+            chunks.push(chunk);
+        }
+    }
+    let init = js_sys::Object::new();
+    Reflect::set(
+        &init,
+        &JsValue::from_str("status"),
+        &JsValue::from(response.status()),
+    )?;
+    Reflect::set(
+        &init,
+        &JsValue::from_str("statusText"),
+        &JsValue::from_str(&response.status_text()),
+    )?;
+    Reflect::set(&init, &JsValue::from_str("headers"), &headers)?;
+    // Responses with status 204, 205 or 304 must not have a body.
+    let body = if bytes == 0.0 {
+        JsValue::null()
+    } else {
+        let body = js_sys::Uint8Array::new_with_length(bytes as u32);
+        let mut offset = 0;
+        for chunk in chunks {
+            body.set(&chunk, offset);
+            offset += chunk.length();
+        }
+        JsValue::from(body)
+    };
+    let limited = construct("Response", &body, &init)?;
+    for key in ["url", "redirected"] {
+        let descriptor = js_sys::Object::new();
+        Reflect::set(
+            &descriptor,
+            &JsValue::from_str("value"),
+            &Reflect::get(&response, &JsValue::from_str(key))?,
+        )?;
+        js_sys::Object::define_property(
+            &js_sys::Object::from(limited.clone()),
+            &JsValue::from_str(key),
+            &descriptor,
+        );
+    }
+    Ok((limited, bytes))
+}
+
+/// Returns `None` if a response of a request with manual redirects is no redirect, otherwise
+/// the absolute URL it redirects to if it is visible.
+fn redirect_location(
+    request: &JsValue,
+    response: &web_sys::Response,
+) -> Result<Option<Option<String>>, Error> {
+    let kind = Reflect::get(response, &JsValue::from_str("type"))?.as_string();
+    if kind.as_deref() == Some("opaqueredirect") {
+        return Ok(Some(None));
+    }
+    if !matches!(response.status(), 301 | 302 | 303 | 307 | 308) {
+        return Ok(None);
+    }
+    let headers = Reflect::get(response, &JsValue::from_str("headers"))?;
+    let location = method(&headers, "get")?
+        .call1(&headers, &JsValue::from_str("location"))?
+        .as_string();
+    let base = Reflect::get(request, &JsValue::from_str("url"))?
+        .as_string()
+        .unwrap_or_default();
+    match location {
+        Some(location) => Ok(Some(
+            web_sys::Url::new_with_base(&location, &base)
+                .ok()
+                .map(|x| x.href()),
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Returns the request that follows a redirect. As with `fetch`, 303 responses and 301 or 302
+/// responses to `POST` requests are followed with a `GET` request without body.
+fn redirected_request(request: &JsValue, location: &str, status: u16) -> Result<JsValue, Error> {
+    let request_method = Reflect::get(request, &JsValue::from_str("method"))?
+        .as_string()
+        .unwrap_or_default();
+    if status == 303 || (matches!(status, 301 | 302) && request_method == "POST") {
+        let init = js_sys::Object::new();
+        Reflect::set(
+            &init,
+            &JsValue::from_str("method"),
+            &JsValue::from_str("GET"),
+        )?;
+        for key in ["headers", "credentials", "redirect"] {
+            Reflect::set(
+                &init,
+                &JsValue::from_str(key),
+                &Reflect::get(request, &JsValue::from_str(key))?,
+            )?;
+        }
+        construct("Request", &JsValue::from_str(location), &init)
+    } else {
+        construct("Request", &JsValue::from_str(location), request)
+    }
+}
+
+/// Clones a request before it is sent, so that its body can be sent again after a redirect.
+fn clone_request(request: &JsValue) -> Result<JsValue, Error> {
+    Ok(method(request, "clone")?.call0(request)?)
+}
+
+/// Cancels reading the body of a response that is rejected, so that it is not downloaded.
+fn cancel_body(response: &web_sys::Response) -> Result<(), Error> {
+    let stream = Reflect::get(response, &JsValue::from_str("body"))?;
+    if !stream.is_null() {
+        // The promise of the cancellation is rejected if the body is read already.
+        let cancelled = js_sys::Promise::from(method(&stream, "cancel")?.call0(&stream)?);
+        wasm_bindgen_futures::spawn_local(async move {
+            let _ = JsFuture::from(cancelled).await;
+        });
+    }
+    Ok(())
+}
+
+fn method(object: &JsValue, name: &str) -> Result<js_sys::Function, Error> {
+    Ok(Reflect::get(object, &JsValue::from_str(name))?.dyn_into::<js_sys::Function>()?)
+}
+
+fn construct(constructor: &str, first: &JsValue, second: &JsValue) -> Result<JsValue, Error> {
+    let constructor = Reflect::get(&js_sys::global(), &JsValue::from_str(constructor))?;
+    Ok(Reflect::construct(
+        &js_sys::Function::from(constructor),
+        &js_sys::Array::of2(first, second),
+    )?)
+}
+
+fn policy_error(message: &str) -> Error {
+    Error::from(js_sys::TypeError::new(&format!(
+        "TypeError: Invalid fetch policy: {}",
+        message
+    )))
+}
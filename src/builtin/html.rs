@@ -0,0 +1,219 @@
+use crate::environment::{RcRealm, Realm};
+use js_sys::{Error, Reflect};
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use wasm_bindgen::prelude::*;
+
+/// Creates the output builders. Markup is parsed in an inert template, so that nothing is
+/// loaded or run before it is sanitized, and the nodes are then adopted by the document.
+/// Sanitizing removes elements that run code or navigate the page, style sheets, whose rules
+/// would apply to the whole page, event handler attributes and URLs with schemes other than
+/// http, https, mailto and tel. Media attributes also accept images as `data:` and `blob:` URLs.
+const OUTPUT_BUILDERS: &str = r#"
+const SVG_NS = "http://www.w3.org/2000/svg";
+const BLOCKED = new Set(["script", "iframe", "frame", "frameset", "object", "embed", "applet",
+  "base", "link", "meta", "noscript", "portal", "style"]);
+const LINKS = new Set(["href", "xlink:href", "action", "formaction", "cite"]);
+const MEDIA = new Set(["src", "poster", "background", "data", "srcset"]);
+
+const safeUrl = (value, media) => {
+  const url = String(value).replace(/[\u0000-\u0020\u007f]/g, "");
+  const scheme = /^([a-z][a-z0-9+.-]*):/i.exec(url);
+  if (!scheme) {
+    return true;
+  }
+  switch (scheme[1].toLowerCase()) {
+    case "http": case "https": case "mailto": case "tel": return true;
+    case "blob": return media;
+    case "data": return media && /^data:image\/(png|gif|jpeg|webp|bmp|avif)[;,]/i.test(url);
+    default: return false;
+  }
+};
+
+const safeAttribute = (name, value) => {
+  name = name.toLowerCase();
+  if (name.startsWith("on") || name === "srcdoc") {
+    return false;
+  }
+  if (name === "style") {
+    return !/expression\s*\(|javascript:/i.test(value);
+  }
+  if (LINKS.has(name)) {
+    return safeUrl(value, false);
+  }
+  if (name === "srcset") {
+    return String(value).split(",").every((x) => safeUrl(x.trim().split(/\s+/)[0], true));
+  }
+  if (MEDIA.has(name)) {
+    return safeUrl(value, true);
+  }
+  // SVG animations can set links, e.g. <set attributeName="href" to="javascript:...">.
+  if (name === "attributename" && /(^|:)href$/i.test(value)) {
+    return false;
+  }
+  return true;
+};
+
+const blocked = (node) => BLOCKED.has(node.localName.toLowerCase());
+
+const clean = (element) => {
+  for (const attribute of [...element.attributes]) {
+    if (!safeAttribute(attribute.name, attribute.value)) {
+      element.removeAttribute(attribute.name);
+    }
+  }
+};
+
+const walk = (parent) => {
+  for (const child of [...parent.childNodes]) {
+    if (child.nodeType === Node.ELEMENT_NODE && !blocked(child)) {
+      clean(child);
+      walk(child.content || child);
+    } else if (child.nodeType !== Node.TEXT_NODE) {
+      child.remove();
+    }
+  }
+};
+
+const sanitizeNode = (node) => {
+  if (isTrusted() || typeof Node === "undefined" || !(node instanceof Node)) {
+    return node;
+  }
+  if (node.nodeType === Node.ELEMENT_NODE) {
+    if (blocked(node)) {
+      return document.createDocumentFragment();
+    }
+    clean(node);
+  }
+  walk(node.content || node);
+  return node;
+};
+
+// Outputs are sanitized as a detached copy, which the cell has no reference to.
+const sanitize = (node) => {
+  if (isTrusted() || typeof Node === "undefined" || !(node instanceof Node)) {
+    return node;
+  }
+  return sanitizeNode(node.cloneNode(true));
+};
+
+const sanitizeMarkup = (markup) => {
+  if (isTrusted()) {
+    return markup;
+  }
+  if (typeof Node === "undefined") {
+    return undefined;
+  }
+  const template = document.createElement("template");
+  template.innerHTML = markup;
+  walk(template.content);
+  return template.innerHTML;
+};
+
+const escape = (value) => String(value).replace(/[&<>"']/g, (x) => "&#" + x.charCodeAt(0) + ";");
+
+const parse = (strings, values, svg) => {
+  if (!Array.isArray(strings)) {
+    strings = [String(strings)];
+    values = [];
+  }
+  const slots = [];
+  let markup = strings[0];
+  values.forEach((value, i) => {
+    for (const x of [value].flat(Infinity)) {
+      if (typeof Node !== "undefined" && x instanceof Node) {
+        markup += "<!--weblab-slot-" + slots.length + "-->";
+        slots.push(x);
+      } else if (x !== null && x !== undefined && x !== false) {
+        markup += escape(x);
+      }
+    }
+    markup += strings[i + 1];
+  });
+  const template = document.createElement("template");
+  template.innerHTML = svg ? "<svg xmlns=\"" + SVG_NS + "\">" + markup + "</svg>" : markup;
+  let content = template.content;
+  if (svg) {
+    const fragment = content.ownerDocument.createDocumentFragment();
+    fragment.append(...content.firstChild.childNodes);
+    content = fragment;
+  }
+  const walker = content.ownerDocument.createTreeWalker(content, NodeFilter.SHOW_COMMENT);
+  const comments = [];
+  while (walker.nextNode()) {
+    comments.push(walker.currentNode);
+  }
+  for (const comment of comments) {
+    const slot = /^weblab-slot-(\d+)$/.exec(comment.data);
+    if (slot) {
+      comment.replaceWith(slots[Number(slot[1])]);
+    }
+  }
+  sanitizeNode(content);
+  const fragment = document.adoptNode(content);
+  const elements = [...fragment.childNodes].filter((x) => x.nodeType === Node.ELEMENT_NODE || x.textContent.trim());
+  return elements.length === 1 && elements[0].nodeType === Node.ELEMENT_NODE ? elements[0] : fragment;
+};
+
+const html = (strings, ...values) => parse(strings, values, false);
+
+const svg = (strings, ...values) => parse(strings, values, true);
+
+const element = (tag, attributes, ...children) => {
+  tag = String(tag);
+  const node = tag.startsWith("svg:")
+    ? document.createElementNS(SVG_NS, tag.slice(4))
+    : document.createElement(tag);
+  if (!isTrusted() && blocked(node)) {
+    throw new TypeError("TypeError: element() cannot create <" + node.localName + "> elements.");
+  }
+  for (const [name, value] of Object.entries(attributes ?? {})) {
+    if (isTrusted() || safeAttribute(name, String(value))) {
+      node.setAttribute(name, value);
+    }
+  }
+  for (const child of children.flat(Infinity)) {
+    if (typeof Node !== "undefined" && child instanceof Node) {
+      node.append(sanitizeNode(child));
+    } else if (child !== null && child !== undefined && child !== false) {
+      node.append(String(child));
+    }
+  }
+  return node;
+};
+
+return { html, svg, element, sanitize, sanitizeMarkup };
+"#;
+
+/// Returns the `html`, `svg` and `element` builtins of a realm and the functions sanitizing
+/// outputs and markup, creating them on first use.
+pub(crate) fn output_builders(realm: &RcRealm) -> js_sys::Object {
+    let builders = realm.borrow().output_builders.clone();
+    builders.unwrap_or_else(|| {
+        let builders = js_sys::Function::new_with_args("isTrusted", OUTPUT_BUILDERS)
+            .call1(&JsValue::undefined(), &is_trusted(Rc::downgrade(realm)))
+            .map(js_sys::Object::from)
+            .unwrap();
+        realm.borrow_mut().output_builders = Some(builders.clone());
+        builders
+    })
+}
+
+/// Returns a sanitized copy of a DOM node output by a cell, so that changes the cell makes to
+/// the node later, e.g. setting an event handler attribute or its `innerHTML`, do not reach the
+/// rendered output. Trusted outputs are returned as they are and stay live, other values are
+/// returned unchanged.
+pub(crate) fn sanitize(realm: &RcRealm, output: JsValue) -> Result<JsValue, Error> {
+    let sanitize = Reflect::get(&output_builders(realm), &JsValue::from_str("sanitize"))?;
+    Ok(js_sys::Function::from(sanitize).call1(&JsValue::undefined(), &output)?)
+}
+
+fn is_trusted(realm: Weak<RefCell<Realm>>) -> js_sys::Function {
+    Closure::wrap(Box::new(move || {
+        realm
+            .upgrade()
+            .is_some_and(|x| x.borrow().config.trust_output)
+    }) as Box<dyn FnMut() -> bool>)
+    .into_js_value()
+    .unchecked_into()
+}
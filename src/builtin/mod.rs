@@ -1 +1,6 @@
+pub mod console;
+pub mod deterministic;
+pub mod display;
+pub mod fetch;
+pub mod html;
 pub mod iterator;
@@ -0,0 +1,250 @@
+use crate::environment::RcRealm;
+use crate::value::Value;
+use js_sys::{Error, Reflect};
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+/// Creates the inspector, which previews values in bounded strings like the console of
+/// DevTools, e.g. `Map(2) {"a" => 1, "b" => 2}` or `Point {x: 1, y: 2}`, and lists the
+/// entries of objects the host expands. Getters are not called in previews.
+const INSPECTOR: &str = r#"
+const ENTRIES = 10;
+const LENGTH = 200;
+const EXPANDED = 100;
+
+const bounded = (text) => text.length > LENGTH ? text.slice(0, LENGTH - 1) + "…" : text;
+
+const expandable = (x) => (typeof x === "object" && x !== null) || typeof x === "function";
+
+const functionText = (x) => {
+  let source = "";
+  try {
+    source = Function.prototype.toString.call(x);
+  } catch (_) {}
+  return /^class[\s{]/.test(source) ? "class " + (x.name || "anonymous") : "ƒ " + (x.name || "anonymous") + "()";
+};
+
+const key = (k) => typeof k === "symbol"
+  ? "[" + k.toString() + "]"
+  : /^[A-Za-z_$][\w$]*$/.test(k) ? k : JSON.stringify(k);
+
+const list = (parts, total) => parts.join(", ") + (total > parts.length ? ", …" : "");
+
+const properties = (x) => {
+  const keys = [];
+  for (const k of Reflect.ownKeys(x)) {
+    if (keys.length > ENTRIES) {
+      break;
+    }
+    const descriptor = Reflect.getOwnPropertyDescriptor(x, k);
+    if (descriptor.enumerable) {
+      keys.push(key(k) + ": " + ("value" in descriptor ? short(descriptor.value) : "(...)"));
+    }
+  }
+  return list(keys.slice(0, ENTRIES), keys.length);
+};
+
+const objectText = (x) => {
+  if (Array.isArray(x)) {
+    const items = [];
+    for (let i = 0; i < Math.min(x.length, ENTRIES); i++) {
+      items.push(i in x ? short(x[i]) : "empty");
+    }
+    return "(" + x.length + ") [" + list(items, x.length) + "]";
+  }
+  if (ArrayBuffer.isView(x) && !(x instanceof DataView)) {
+    return typeTag(x) + "(" + x.length + ") [" + list([...x.subarray(0, ENTRIES)].map(short), x.length) + "]";
+  }
+  if (x instanceof Map) {
+    const entries = [];
+    for (const [k, v] of x) {
+      if (entries.length === ENTRIES) {
+        break;
+      }
+      entries.push(short(k) + " => " + short(v));
+    }
+    return typeTag(x) + "(" + x.size + ") {" + list(entries, x.size) + "}";
+  }
+  if (x instanceof Set) {
+    const values = [];
+    for (const v of x) {
+      if (values.length === ENTRIES) {
+        break;
+      }
+      values.push(short(v));
+    }
+    return typeTag(x) + "(" + x.size + ") {" + list(values, x.size) + "}";
+  }
+  if (x instanceof Date) {
+    return date(x);
+  }
+  if (x instanceof RegExp) {
+    return String(x);
+  }
+  if (x instanceof Error) {
+    return errorText(x);
+  }
+  if (x instanceof Promise) {
+    return "Promise";
+  }
+  if (defined("Node") && x instanceof Node) {
+    return x.nodeType === Node.ELEMENT_NODE ? "<" + x.localName + ">" : x.nodeName;
+  }
+  const name = constructorName(x);
+  return (name && name !== "Object" ? name + " " : "") + "{" + properties(x) + "}";
+};
+
+const preview = (x) => {
+  if (typeof x === "string") {
+    return bounded(JSON.stringify(x));
+  }
+  if (typeof x === "function") {
+    return bounded(functionText(x));
+  }
+  if (typeof x !== "object" || x === null) {
+    return short(x);
+  }
+  try {
+    return bounded(objectText(x));
+  } catch (_) {
+    return typeTag(x);
+  }
+};
+
+const entry = (k, value) => ({ key: k, preview: preview(value), type: typeTag(value), expandable: expandable(value) });
+
+// Returns the child of a value the host expanded: an entry of a map, a value of a set or a
+// property, whose getter is called.
+const child = (x, k) => {
+  if (x instanceof Map || x instanceof Set) {
+    const i = Number(k);
+    const values = Number.isInteger(i) && i >= 0 && i < x.size ? [...x] : [];
+    if (i < values.length) {
+      return x instanceof Map ? { key: values[i][0], value: values[i][1] } : values[i];
+    }
+  } else {
+    const found = Reflect.ownKeys(x).find((name) => typeof name === "symbol" ? "[" + name.toString() + "]" === k : name === k);
+    if (found !== undefined) {
+      return Reflect.get(x, found);
+    }
+  }
+  throw new ReferenceError("ReferenceError: " + preview(x) + " has no entry " + JSON.stringify(k) + ".");
+};
+
+const inspect = (x, path) => {
+  for (const k of path) {
+    if (!expandable(x)) {
+      throw new TypeError("TypeError: " + preview(x) + " has no entries.");
+    }
+    x = child(x, String(k));
+  }
+  const entries = [];
+  let total;
+  if (x instanceof Map || x instanceof Set) {
+    total = x.size;
+    for (const value of x) {
+      if (entries.length === EXPANDED) {
+        break;
+      }
+      const i = String(entries.length);
+      entries.push(x instanceof Map
+        ? { ...entry(i, { key: value[0], value: value[1] }), preview: short(value[0]) + " => " + short(value[1]) }
+        : entry(i, value));
+    }
+  } else if (expandable(x)) {
+    const keys = Reflect.ownKeys(x);
+    total = keys.length;
+    for (const k of keys.slice(0, EXPANDED)) {
+      const descriptor = Reflect.getOwnPropertyDescriptor(x, k);
+      const name = typeof k === "symbol" ? "[" + k.toString() + "]" : k;
+      entries.push("value" in descriptor
+        ? { ...entry(name, descriptor.value), enumerable: descriptor.enumerable }
+        : { key: name, preview: "(...)", type: "accessor", expandable: true, enumerable: descriptor.enumerable });
+    }
+  } else {
+    total = 0;
+  }
+  return { type: typeTag(x), preview: preview(x), entries, more: total - entries.length };
+};
+
+return { preview, inspect };
+"#;
+
+/// Values of cell results the host can inspect, by handle. Handles of a cell are released
+/// when it is re-run or deleted, those of cells without id when the next cell without id
+/// runs, and all handles when the environments are reset.
+#[derive(Debug, Default)]
+pub struct Handles {
+    next: u32,
+    values: HashMap<u32, (Option<String>, JsValue)>,
+}
+
+impl Handles {
+    pub fn insert(&mut self, cell: Option<String>, value: JsValue) -> u32 {
+        self.next += 1;
+        self.values.insert(self.next, (cell, value));
+        self.next
+    }
+
+    pub fn get(&self, handle: u32) -> Option<&JsValue> {
+        self.values.get(&handle).map(|(_, value)| value)
+    }
+
+    /// Releases the handles of the results of a cell, of the cells without id for `""`.
+    pub fn release(&mut self, cell: &str) {
+        self.values
+            .retain(|_, (owner, _)| owner.as_deref().unwrap_or_default() != cell);
+    }
+}
+
+/// Returns the preview of a value, at most 200 characters long.
+pub(crate) fn preview(value: &Value, realm: &RcRealm) -> Result<String, Error> {
+    let preview = Reflect::get(&inspector(realm), &JsValue::from_str("preview"))?;
+    js_sys::Function::from(preview)
+        .call1(&JsValue::undefined(), value.as_ref())?
+        .as_string()
+        .ok_or_else(|| Error::new("ERROR: The preview is not a string."))
+}
+
+/// Returns a handle the host can inspect a value through, `None` for values without
+/// entries such as numbers and strings.
+pub(crate) fn handle(value: &Value, cell: Option<String>, realm: &RcRealm) -> Option<u32> {
+    let value: &JsValue = value.as_ref();
+    (value.is_object() || value.is_function())
+        .then(|| realm.borrow_mut().handles.insert(cell, value.clone()))
+}
+
+/// Returns the type, the preview and the first 100 entries of the value found by following
+/// the keys of `path` from the value of `handle`, with the number of entries left out.
+pub(crate) fn inspect(realm: &RcRealm, handle: u32, path: &JsValue) -> Result<JsValue, Error> {
+    let value = realm.borrow().handles.get(handle).cloned().ok_or_else(|| {
+        Error::from(js_sys::ReferenceError::new(&format!(
+            "ReferenceError: There is no value with the handle {}.",
+            handle
+        )))
+    })?;
+    let path = if path.is_null() || path.is_undefined() {
+        js_sys::Array::new()
+    } else if js_sys::Array::is_array(path) {
+        js_sys::Array::from(path)
+    } else {
+        return Err(Error::from(js_sys::TypeError::new(
+            "TypeError: The path must be an array of keys.",
+        )));
+    };
+    let inspect = Reflect::get(&inspector(realm), &JsValue::from_str("inspect"))?;
+    Ok(js_sys::Function::from(inspect).call2(&JsValue::undefined(), &value, &path)?)
+}
+
+/// Returns the inspector of a realm, creating it on first use.
+pub(crate) fn inspector(realm: &RcRealm) -> js_sys::Object {
+    let inspector = realm.borrow().inspector.clone();
+    inspector.unwrap_or_else(|| {
+        let inspector = js_sys::Function::new_no_args(&[super::PRELUDE, INSPECTOR].concat())
+            .call0(&JsValue::undefined())
+            .map(js_sys::Object::from)
+            .unwrap();
+        realm.borrow_mut().inspector = Some(inspector.clone());
+        inspector
+    })
+}
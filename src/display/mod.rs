@@ -0,0 +1,509 @@
+pub mod inspect;
+
+use crate::builtin::html;
+use crate::environment::RcRealm;
+use crate::evaluator::expressions::unary::eval_typeof_operator;
+use crate::value::Value;
+use js_sys::{Error, Reflect};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+/// Helpers of the renderers and the inspector, which previews values with `short` where they
+/// are nested in other previews, e.g. `Map(1)` or `{…}`.
+const PRELUDE: &str = r#"
+const defined = (name) => typeof globalThis[name] === "function";
+
+const constructorName = (x) => {
+  try {
+    const constructor = x.constructor;
+    return typeof constructor === "function" && constructor.name ? constructor.name : undefined;
+  } catch (_) {
+    return undefined;
+  }
+};
+
+const typeTag = (x) => x === null ? "null" : typeof x === "object" ? constructorName(x) || "Object" : typeof x;
+
+const date = (x) => isNaN(x) ? "Invalid Date" : x.toISOString();
+
+const errorText = (x) => {
+  const name = String(x.name);
+  const message = String(x.message);
+  return message.startsWith(name + ":") ? message : name + ": " + message;
+};
+
+// Previews values nested in a preview, without their entries.
+const short = (x) => {
+  switch (typeof x) {
+    case "string": return JSON.stringify(x.length > 50 ? x.slice(0, 49) + "…" : x);
+    case "bigint": return x + "n";
+    case "symbol": return x.toString();
+    case "function": return "ƒ";
+    case "object": break;
+    default: return Object.is(x, -0) ? "-0" : String(x);
+  }
+  if (x === null) {
+    return "null";
+  }
+  if (Array.isArray(x)) {
+    return "Array(" + x.length + ")";
+  }
+  if (x instanceof Map || x instanceof Set) {
+    return typeTag(x) + "(" + x.size + ")";
+  }
+  if (x instanceof Date) {
+    return date(x);
+  }
+  if (x instanceof RegExp) {
+    return String(x);
+  }
+  const name = constructorName(x);
+  return !name || name === "Object" ? "{…}" : name;
+};
+"#;
+
+/// Creates the function rendering values as MIME bundles. Objects with a
+/// `[Symbol.for("display")]()` method render themselves: it returns a bundle, a promise of a
+/// bundle or a string for `text/plain`. Other values are rendered by the first built-in
+/// renderer that accepts them. `text/plain` falls back to the preview of the inspector for
+/// objects and to the output of `eval_cell` for other values. `application/json` is left out
+/// for values with more entries than the table limits allow in total.
+const RENDERERS: &str = r#"
+const DISPLAY = Symbol.for("display");
+const LIMIT = 100;
+const MIME_TYPE = /^[\w.+-]+\/[\w.+-]+$/;
+
+const escape = (value) => String(value).replace(/[&<>"']/g, (x) => "&#" + x.charCodeAt(0) + ";");
+
+const list = (items, size) => items.slice(0, LIMIT).join(", ") + (size > LIMIT ? ", …" : "");
+
+const table = (headers, rows) => "<table><thead><tr>"
+  + headers.map((x) => "<th>" + escape(x) + "</th>").join("")
+  + "</tr></thead><tbody>"
+  + rows.slice(0, LIMIT).map((row) => "<tr>" + row.map((x) => "<td>" + escape(x) + "</td>").join("") + "</tr>").join("")
+  + "</tbody></table>";
+
+const json = (value) => {
+  try {
+    const text = JSON.stringify(value);
+    return text === undefined ? undefined : JSON.parse(text);
+  } catch (_) {
+    return undefined;
+  }
+};
+
+// Returns the JSON of a value like `json`, `undefined` if its arrays and objects have more
+// than `maxRows × maxColumns` entries in total, so that large values are not copied.
+const boundedJson = (value, limits) => {
+  const limit = limits.maxRows * limits.maxColumns;
+  const pending = [value];
+  const seen = new Set();
+  let count = 0;
+  while (pending.length > 0) {
+    const x = pending.pop();
+    if (typeof x !== "object" || x === null || seen.has(x)) {
+      continue;
+    }
+    seen.add(x);
+    if (ArrayBuffer.isView(x)) {
+      // Typed arrays only hold numbers.
+      count += x.length || 0;
+      continue;
+    }
+    const children = Array.isArray(x) ? x : Object.values(x);
+    count += children.length;
+    if (count > limit) {
+      return undefined;
+    }
+    for (const child of children) {
+      pending.push(child);
+    }
+  }
+  return json(value);
+};
+
+const png = (canvas) => {
+  const url = canvas.toDataURL("image/png");
+  return url.slice(url.indexOf(",") + 1);
+};
+
+const settle = async (promise) => {
+  const pending = {};
+  try {
+    const value = await Promise.race([promise, Promise.resolve(pending)]);
+    return value === pending ? "<pending>" : "<fulfilled>: " + short(value);
+  } catch (error) {
+    return "<rejected>: " + (error instanceof Error ? errorText(error) : short(error));
+  }
+};
+
+const plain = (x) => typeof x === "object" && x !== null
+  && [Object.prototype, null].includes(Object.getPrototypeOf(x));
+
+const values = (x) => Array.isArray(x) ? x : ArrayBuffer.isView(x) && !(x instanceof DataView) ? [...x] : undefined;
+
+// Returns the column names and rows of an array of records or of an object of equally long
+// arrays, `undefined` for other values.
+const records = (x, maxRows) => {
+  if (Array.isArray(x)) {
+    if (x.length === 0 || !x.every(plain)) {
+      return undefined;
+    }
+    const names = [...new Set(x.flatMap(Object.keys))];
+    const rows = x.slice(0, maxRows).map((row) => names.map((name) => row[name]));
+    return names.length === 0 ? undefined : { names, rows, total: x.length };
+  }
+  if (plain(x)) {
+    const names = Object.keys(x);
+    const columns = names.map((name) => values(x[name]));
+    if (names.length === 0 || columns.some((column) => !column || column.length !== columns[0].length)) {
+      return undefined;
+    }
+    const total = columns[0].length;
+    const rows = columns[0].slice(0, maxRows).map((_, i) => columns.map((column) => column[i]));
+    return total === 0 ? undefined : { names, rows, total };
+  }
+  return undefined;
+};
+
+const kind = (value) => value instanceof Date ? "date" : Array.isArray(value) ? "array" : typeof value;
+
+// Infers the type of a column from its values, ignoring missing ones.
+const columnType = (rows, i) => {
+  const kinds = new Set(rows.map((row) => row[i]).filter((x) => x !== null && x !== undefined).map(kind));
+  return kinds.size === 0 ? "null" : kinds.size === 1 ? [...kinds][0] : "mixed";
+};
+
+// Types of Table Schema, which data resources describe their fields with.
+const FIELD_TYPES = { number: "number", bigint: "integer", string: "string", boolean: "boolean",
+  date: "datetime", array: "array", object: "object" };
+
+// Returns the rows as Table Schema data resource, with the types of the columns as fields.
+const dataResource = ({ names, rows }) => ({
+  schema: { fields: names.map((name, i) => ({ name, type: FIELD_TYPES[columnType(rows, i)] || "any" })) },
+  data: json(rows.map((row) => Object.fromEntries(names.map((name, i) =>
+    [name, typeof row[i] === "bigint" ? String(row[i]) : row[i]])))),
+});
+
+const cellText = (value, maxWidth) => {
+  let text;
+  if (value === undefined) {
+    text = "";
+  } else if (typeof value === "string") {
+    text = value;
+  } else if (typeof value === "object" && value !== null && !(value instanceof Date)) {
+    text = json(value) === undefined ? short(value) : JSON.stringify(value);
+  } else {
+    text = short(value);
+  }
+  text = text.replace(/\s+/g, " ");
+  return text.length > maxWidth ? text.slice(0, Math.max(maxWidth - 1, 0)) + "…" : text;
+};
+
+const recordTable = ({ names, rows, total }, limits) => {
+  const columns = Math.min(names.length, limits.maxColumns);
+  const types = names.slice(0, columns).map((_, i) => columnType(rows, i));
+  const right = types.map((type) => type === "number" || type === "bigint");
+  const header = names.slice(0, columns).map((name) => cellText(name, limits.maxWidth));
+  const cells = rows.map((row) => row.slice(0, columns).map((value) => cellText(value, limits.maxWidth)));
+  // Describes what is left out after the first rows.
+  const more = (shown) => {
+    const hidden = [];
+    if (total > shown) {
+      hidden.push((total - shown) + (total - shown === 1 ? " more row" : " more rows"));
+    }
+    if (names.length > columns) {
+      hidden.push((names.length - columns) + (names.length - columns === 1 ? " more column" : " more columns"));
+    }
+    return hidden.length ? "… " + hidden.join(", ") : undefined;
+  };
+
+  const lines = [header, ...cells.slice(0, limits.pageSize)];
+  const widths = header.map((_, i) => Math.max(...lines.map((line) => line[i].length)));
+  const align = (line) => line.map((text, i) => right[i] ? text.padStart(widths[i]) : text.padEnd(widths[i]))
+    .join("  ").trimEnd();
+  const text = [align(header), widths.map((width) => "-".repeat(width)).join("  "), ...lines.slice(1).map(align)];
+  const textNote = more(lines.length - 1);
+  if (textNote) {
+    text.push(textNote);
+  }
+
+  const style = (i) => right[i] ? " style=\"text-align: right\"" : "";
+  const head = "<thead><tr>" + header.map((name, i) => "<th" + style(i) + ">" + escape(name)
+    + "<br><small>" + types[i] + "</small></th>").join("") + "</tr></thead>";
+  const page = (start) => "<table>" + head + "<tbody>" + cells.slice(start, start + limits.pageSize)
+    .map((row) => "<tr>" + row.map((x, i) => "<td" + style(i) + ">" + escape(x) + "</td>").join("") + "</tr>")
+    .join("") + "</tbody></table>";
+  let html = page(0);
+  for (let start = limits.pageSize; start < cells.length; start += limits.pageSize) {
+    const end = Math.min(start + limits.pageSize, cells.length);
+    html += "<details><summary>" + (end > start + 1 ? "Rows " + (start + 1) + "–" + end : "Row " + end)
+      + "</summary>" + page(start) + "</details>";
+  }
+  const note = more(cells.length);
+  if (note) {
+    html += "<p>" + escape(note) + "</p>";
+  }
+  return {
+    "text/plain": text.join("\n"),
+    "text/html": "<div>" + html + "</div>",
+    "application/vnd.dataresource+json": dataResource({ names, rows }),
+  };
+};
+
+const renderers = [
+  [(x) => x instanceof Map, (x) => ({
+    "text/plain": "Map(" + x.size + ") {"
+      + list([...x].map(([key, value]) => short(key) + " => " + short(value)), x.size) + "}",
+    "text/html": table(["Key", "Value"], [...x].map((entry) => entry.map(short))),
+  })],
+  [(x) => x instanceof Set, (x, limits) => ({
+    "text/plain": "Set(" + x.size + ") {" + list([...x].map(short), x.size) + "}",
+    "text/html": table(["Value"], [...x].map((value) => [short(value)])),
+    "application/json": x.size > limits.maxRows * limits.maxColumns ? undefined : boundedJson([...x], limits),
+  })],
+  [(x) => ArrayBuffer.isView(x) && !(x instanceof DataView), (x) => ({
+    "text/plain": x.constructor.name + "(" + x.length + ") ["
+      + list([...x.subarray(0, LIMIT)].map(short), x.length) + "]",
+  })],
+  [(x) => x instanceof Date, (x) => ({
+    "text/plain": short(x),
+    "application/json": isNaN(x) ? null : x.toISOString(),
+  })],
+  [(x) => x instanceof Error, (x) => ({
+    "text/plain": errorText(x),
+    "text/html": "<pre>" + escape(errorText(x)) + "</pre>",
+  })],
+  [(x) => x instanceof Promise, async (x) => ({
+    "text/plain": "Promise {" + await settle(x) + "}",
+  })],
+  [(x) => defined("ImageData") && x instanceof ImageData, (x) => {
+    const bundle = { "text/plain": "ImageData(" + x.width + "×" + x.height + ")" };
+    if (typeof document !== "undefined") {
+      const canvas = document.createElement("canvas");
+      canvas.width = x.width;
+      canvas.height = x.height;
+      canvas.getContext("2d").putImageData(x, 0, 0);
+      bundle["image/png"] = png(canvas);
+    }
+    return bundle;
+  }],
+  [(x) => defined("HTMLCanvasElement") && x instanceof HTMLCanvasElement, (x) => ({
+    "text/plain": "HTMLCanvasElement(" + x.width + "×" + x.height + ")",
+    "image/png": png(x),
+  })],
+  [(x) => defined("Node") && x instanceof Node, (x) => {
+    let markup = x.outerHTML;
+    if (markup === undefined) {
+      const container = document.createElement("div");
+      container.append(x.cloneNode(true));
+      markup = container.innerHTML;
+    }
+    const bundle = { "text/html": markup, "text/plain": x.textContent };
+    if (defined("SVGSVGElement") && x instanceof SVGSVGElement) {
+      bundle["image/svg+xml"] = markup;
+    }
+    return bundle;
+  }],
+  [(x, limits) => records(x, limits.maxRows) !== undefined, (x, limits) => ({
+    ...recordTable(records(x, limits.maxRows), limits),
+    "application/json": boundedJson(x, limits),
+  })],
+  [(x) => typeof x === "object" && x !== null, (x, limits) => ({ "application/json": boundedJson(x, limits) })],
+];
+
+const hook = async (value) => {
+  let bundle = await value[DISPLAY]();
+  if (typeof bundle === "string") {
+    return { "text/plain": bundle };
+  }
+  if (typeof bundle !== "object" || bundle === null) {
+    throw new TypeError("TypeError: [Symbol.for(\"display\")]() must return an object of MIME types or a string.");
+  }
+  const result = {};
+  for (const [type, data] of Object.entries(bundle)) {
+    if (!MIME_TYPE.test(type)) {
+      throw new TypeError("TypeError: " + JSON.stringify(type) + " is not a MIME type.");
+    }
+    if (data !== undefined && data !== null) {
+      result[type] = type.endsWith("json") ? json(data) : String(data);
+    }
+  }
+  for (const type of ["text/html", "image/svg+xml"]) {
+    if (type in result) {
+      result[type] = sanitizeMarkup(result[type]);
+    }
+  }
+  return result;
+};
+
+return async (value, output, limits) => {
+  let bundle = {};
+  if (value !== null && value !== undefined && typeof value[DISPLAY] === "function") {
+    bundle = await hook(value);
+  } else {
+    // DOM nodes are rendered from their sanitized output.
+    const x = defined("Node") && output instanceof Node ? output : value;
+    for (const [accepts, render] of renderers) {
+      if (accepts(x, limits)) {
+        bundle = await render(x, limits);
+        break;
+      }
+    }
+  }
+  for (const type of Object.keys(bundle)) {
+    if (bundle[type] === undefined) {
+      delete bundle[type];
+    }
+  }
+  if (bundle["text/plain"] === undefined) {
+    // The output of objects is their whole JSON, the preview is bounded.
+    bundle["text/plain"] = typeof value === "object" && value !== null
+      ? preview(value)
+      : typeof output === "string" ? output : String(output);
+  }
+  return bundle;
+};
+"#;
+
+/// Limits of the tables arrays of records are rendered as, which the host can change.
+#[derive(Clone, Copy, Debug)]
+pub struct TableLimits {
+    /// Rows of a page of the HTML table, which are also the rows of the text table.
+    pub page_size: u32,
+    /// Rows rendered at all, the others are left out.
+    pub max_rows: u32,
+    pub max_columns: u32,
+    /// Characters of a cell, longer values are truncated.
+    pub max_width: u32,
+}
+
+impl Default for TableLimits {
+    fn default() -> TableLimits {
+        TableLimits {
+            page_size: 20,
+            max_rows: 1000,
+            max_columns: 50,
+            max_width: 50,
+        }
+    }
+}
+
+impl TableLimits {
+    const OPTIONS: [&'static str; 4] = ["pageSize", "maxRows", "maxColumns", "maxWidth"];
+
+    /// Creates limits from the host's options, an object with the optional properties
+    /// `pageSize`, `maxRows`, `maxColumns` and `maxWidth`. Omitted limits keep their default.
+    pub fn from_js(options: &JsValue) -> Result<TableLimits, Error> {
+        if !options.is_object() {
+            return Err(limits_error("The table limits must be an object."));
+        }
+        let mut limits = TableLimits::default();
+        let fields = [
+            &mut limits.page_size,
+            &mut limits.max_rows,
+            &mut limits.max_columns,
+            &mut limits.max_width,
+        ];
+        for (name, field) in TableLimits::OPTIONS.into_iter().zip(fields) {
+            let value = Reflect::get(options, &JsValue::from_str(name))?;
+            match value.as_f64() {
+                Some(value) if value >= 1.0 && value <= u32::MAX as f64 && value.fract() == 0.0 => {
+                    *field = value as u32
+                }
+                None if value.is_undefined() => (),
+                _ => {
+                    return Err(limits_error(&format!(
+                        "{:?} must be a positive integer.",
+                        name
+                    )))
+                }
+            }
+        }
+        Ok(limits)
+    }
+
+    fn to_js(self) -> Result<js_sys::Object, Error> {
+        let limits = js_sys::Object::new();
+        let values = [
+            self.page_size,
+            self.max_rows,
+            self.max_columns,
+            self.max_width,
+        ];
+        for (name, value) in TableLimits::OPTIONS.into_iter().zip(values) {
+            Reflect::set(&limits, &JsValue::from_str(name), &JsValue::from(value))?;
+        }
+        Ok(limits)
+    }
+}
+
+fn limits_error(message: &str) -> Error {
+    Error::from(js_sys::TypeError::new(&format!(
+        "TypeError: Invalid table limits: {}",
+        message
+    )))
+}
+
+/// Returns the MIME bundle of a value, an object mapping MIME types such as `text/plain`,
+/// `text/html`, `image/png` or `application/json` to representations. `output` is the
+/// sanitized output of the value, which DOM nodes are rendered from.
+pub(crate) async fn mime_bundle(
+    value: &Value,
+    output: &JsValue,
+    realm: &RcRealm,
+) -> Result<js_sys::Object, Error> {
+    let bundle = JsFuture::from(render(value, output, realm)?).await?;
+    Ok(js_sys::Object::from(bundle))
+}
+
+/// Starts rendering a value and returns a promise of its MIME bundle. Values are rendered as
+/// they are when it is called, unless their `[Symbol.for("display")]()` method is async.
+pub(crate) fn render(
+    value: &Value,
+    output: &JsValue,
+    realm: &RcRealm,
+) -> Result<js_sys::Promise, Error> {
+    let limits = realm.borrow().config.table_limits.to_js()?;
+    let bundle = renderer(realm).call3(&JsValue::undefined(), value.as_ref(), output, &limits)?;
+    Ok(js_sys::Promise::from(bundle))
+}
+
+/// Returns the function rendering values of a realm, creating it on first use.
+fn renderer(realm: &RcRealm) -> js_sys::Function {
+    let renderer = realm.borrow().renderer.clone();
+    renderer.unwrap_or_else(|| {
+        let sanitize_markup = Reflect::get(
+            &html::output_builders(realm),
+            &JsValue::from_str("sanitizeMarkup"),
+        )
+        .unwrap();
+        let preview =
+            Reflect::get(&inspect::inspector(realm), &JsValue::from_str("preview")).unwrap();
+        let renderer = js_sys::Function::new_with_args(
+            "sanitizeMarkup, preview",
+            &[PRELUDE, RENDERERS].concat(),
+        )
+        .call2(&JsValue::undefined(), &sanitize_markup, &preview)
+        .map(js_sys::Function::from)
+        .unwrap();
+        realm.borrow_mut().renderer = Some(renderer.clone());
+        renderer
+    })
+}
+
+/// Returns the type of a value for the host: the result of `typeof`, except `"null"` for
+/// `null` and the name of the constructor for objects, e.g. `"Array"` or `"Map"`.
+pub(crate) fn type_tag(value: &Value) -> Result<String, Error> {
+    Ok(match value {
+        Value::Null(_) => String::from("null"),
+        Value::Object(object) => Reflect::get(object, &JsValue::from_str("constructor"))
+            .ok()
+            .filter(|x| x.is_function())
+            .and_then(|x| Reflect::get(&x, &JsValue::from_str("name")).ok())
+            .and_then(|x| x.as_string())
+            .filter(|x| !x.is_empty())
+            .unwrap_or_else(|| String::from("Object")),
+        _ => eval_typeof_operator(value)?.to_js_string()?.into(),
+    })
+}
@@ -0,0 +1,109 @@
+use futures::channel::oneshot;
+use futures::future::{self, Either};
+use js_sys::{Error, Reflect};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+/// Interpreted code yields to the event loop at least this often, in milliseconds, so that
+/// the host can interrupt it.
+const YIELD_INTERVAL: f64 = 100.0;
+
+/// Interruption of the cell running in a session, which interpreted code checks at loop
+/// iterations, function calls and `await`.
+#[derive(Clone, Debug, Default)]
+pub struct Interrupt(Rc<RefCell<InterruptState>>);
+
+#[derive(Debug, Default)]
+struct InterruptState {
+    interrupted: bool,
+    /// Time interpreted code last yielded to the event loop.
+    yielded: f64,
+    /// Awaits of interpreted code, which fail as soon as it is interrupted.
+    awaits: Vec<oneshot::Sender<()>>,
+}
+
+impl Interrupt {
+    /// Interrupts the running code at its next check.
+    pub fn interrupt(&self) {
+        let awaits = {
+            let mut state = self.0.borrow_mut();
+            state.interrupted = true;
+            std::mem::take(&mut state.awaits)
+        };
+        for sender in awaits {
+            let _ = sender.send(());
+        }
+    }
+
+    /// Lets code run again, before the next cell.
+    pub fn reset(&self) {
+        let mut state = self.0.borrow_mut();
+        state.interrupted = false;
+        state.yielded = instant::now();
+        state.awaits.clear();
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.0.borrow().interrupted
+    }
+
+    /// Fails if the code was interrupted. Yields to the event loop first if the code ran
+    /// without yielding for a while, so that the host gets to interrupt it.
+    pub async fn check(&self) -> Result<(), Error> {
+        self.status()?;
+        if instant::now() - self.0.borrow().yielded >= YIELD_INTERVAL {
+            JsFuture::from(next_task()).await?;
+            self.0.borrow_mut().yielded = instant::now();
+            self.status()?;
+        }
+        Ok(())
+    }
+
+    /// Awaits a promise, failing as soon as the code is interrupted.
+    pub async fn wait(&self, promise: js_sys::Promise) -> Result<JsValue, Error> {
+        self.status()?;
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut state = self.0.borrow_mut();
+            state.awaits.retain(|x| !x.is_canceled());
+            state.awaits.push(sender);
+        }
+        let result = match future::select(JsFuture::from(promise), receiver).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => return Err(interrupted_error()),
+        };
+        self.0.borrow_mut().yielded = instant::now();
+        self.status()?;
+        Ok(result?)
+    }
+
+    fn status(&self) -> Result<(), Error> {
+        if self.is_interrupted() {
+            Err(interrupted_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Returns the error interrupted cells fail with, which `try` statements do not catch.
+pub fn interrupted_error() -> Error {
+    let error = Error::new("Interrupted: The cell was interrupted.");
+    error.set_name("Interrupted");
+    error
+}
+
+/// Returns a promise resolved in a later task of the event loop.
+fn next_task() -> js_sys::Promise {
+    js_sys::Promise::new(&mut |resolve, _| {
+        let set_timeout = Reflect::get(&js_sys::global(), &JsValue::from_str("setTimeout"))
+            .ok()
+            .and_then(|x| x.dyn_into::<js_sys::Function>().ok());
+        let _ = match set_timeout {
+            Some(set_timeout) => set_timeout.call2(&JsValue::undefined(), &resolve, &0.into()),
+            None => resolve.call0(&JsValue::undefined()),
+        };
+    })
+}
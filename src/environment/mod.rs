@@ -4,203 +4,176 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::slice::{Iter, IterMut};
+use swc_common::Span;
 use wasm_bindgen::prelude::*;
 
+pub use interrupt::{interrupted_error, Interrupt};
+pub use policy::CapabilityPolicy;
+pub use realm::{RcRealm, Realm};
+
+mod interrupt;
+mod policy;
+mod realm;
+
 #[derive(Clone, Debug, Default)]
 pub struct Environments {
     pub stack: Vec<HashMap<String, Rc<RefCell<Value>>>>,
+    pub realm: RcRealm,
+    /// Exports of the module being evaluated, in order of declaration.
+    pub exports: Vec<(String, Export)>,
+    /// URL of the module being evaluated, `None` for cells.
+    pub module: Option<String>,
+    /// Id of the cell being evaluated, `None` for modules and cells without id.
+    pub cell: Option<String>,
+    /// Number of the source being evaluated, which tells apart the call sites of different
+    /// sources at the same position.
+    pub source: u32,
+    /// Top-level names defined by each cell, keyed by cell id, with the values they shadowed,
+    /// e.g. of builtins, which are restored when the cell's bindings are dropped.
+    pub cell_bindings: HashMap<String, HashMap<String, Option<RcValue>>>,
+    /// Span of the innermost statement that failed, for the location of uncaught errors.
+    pub error_span: Option<Span>,
+    /// Names declared with `let` and `const` by the statement lists being evaluated, innermost
+    /// last, with whether their declaration was evaluated.
+    pub lexical: Vec<HashMap<String, bool>>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Export {
+    /// A local binding, which is read when the module namespace is created.
+    Binding(String),
+    /// A value, e.g. of a default export expression or a re-export.
+    Value(Rc<RefCell<Value>>),
 }
 
 impl Environments {
     pub fn new() -> Environments {
+        Environments::with_realm(RcRealm::default())
+    }
+
+    /// Creates the global environment of a cell or module in an existing realm.
+    pub fn with_realm(realm: RcRealm) -> Environments {
+        let source = {
+            let mut realm = realm.borrow_mut();
+            realm.sources += 1;
+            realm.sources
+        };
         let mut envs = Environments {
             stack: vec![HashMap::new()],
+            realm,
+            source,
+            ..Default::default()
         };
-        let global = js_sys::global();
-        let global_proxy = create_global_proxy(&global);
-        let global_this = Rc::new(RefCell::new(Value::from(JsValue::from(global_proxy))));
-        envs.insert("globalThis", global_this.clone()).unwrap();
-        envs.insert("self", global_this.clone()).unwrap();
-        envs.insert("window", global_this.clone()).unwrap();
-        envs.insert("this", global_this).unwrap();
+        envs.insert_globals().unwrap();
 
-        let document_proxy = create_document_proxy(&global).unwrap();
-        let document = Rc::new(RefCell::new(Value::from(JsValue::from(document_proxy))));
-        envs.insert("document", document).unwrap();
+        let builders = crate::builtin::html::output_builders(&envs.realm);
+        for name in ["html", "svg", "element"] {
+            let builder = js_sys::Reflect::get(&builders, &JsValue::from_str(name)).unwrap();
+            envs.insert(name, Rc::new(RefCell::new(Value::from(builder))))
+                .unwrap();
+        }
+
+        let display = crate::builtin::display::display_function(&envs.realm, None);
+        envs.insert(
+            "display",
+            Rc::new(RefCell::new(Value::from(JsValue::from(display)))),
+        )
+        .unwrap();
+
+        let require = crate::evaluator::commonjs::require_function(&envs.realm);
+        envs.insert(
+            "require",
+            Rc::new(RefCell::new(Value::from(JsValue::from(require)))),
+        )
+        .unwrap();
         envs
     }
 
     pub fn empty() -> Environments {
-        Environments { stack: Vec::new() }
+        Environments::default()
     }
-}
 
-#[inline]
-fn create_global_proxy(global: &js_sys::Object) -> js_sys::Proxy {
-    let handler = js_sys::Object::new();
-    let set =
-        js_sys::Function::new_with_args("target, propKey, value, receiver", "return undefined;");
-    let get = js_sys::Function::new_with_args(
-        "target, propKey, receiver",
-        "if (propKey === \"console\"
-            || propKey === \"undefined\" 
-            || propKey === \"fetch\" 
-            || propKey === \"Number\"
-            || propKey === \"Boolean\"
-            || propKey === \"Object\"
-            || propKey === \"Function\"
-            || propKey === \"String\"
-            || propKey === \"Generator\"
-            || propKey === \"Iterator\"
-            || propKey === \"Symbol\"
-            || propKey === \"Math\"
-            || propKey === \"Date\"
-            || propKey === \"RegExp\"
-            || propKey === \"Array\"
-            || propKey === \"Int8Array\"
-            || propKey === \"Uint8Array\"
-            || propKey === \"Uint8ClampedArray\"
-            || propKey === \"Int16Array\"
-            || propKey === \"Uint16Array\"
-            || propKey === \"Int32Array\"
-            || propKey === \"Uint32Array\"
-            || propKey === \"Float32Array\"
-            || propKey === \"Float64Array\"
-            || propKey === \"BigInt64Array\"
-            || propKey === \"BigUint64Array\"
-            || propKey === \"Map\"
-            || propKey === \"Set\"
-            || propKey === \"WeakMap\"
-            || propKey === \"WeakSet\"
-            || propKey === \"ArrayBuffer\"
-            || propKey === \"SharedArrayBuffer\"
-            || propKey === \"Error\"
-            || propKey === \"EvalError\"
-            || propKey === \"RangeError\"
-            || propKey === \"ReferenceError\"
-            || propKey === \"SyntaxError\"
-            || propKey === \"TypeError\"
-            || propKey === \"DataView\"
-            || propKey === \"Promise\"
-            || propKey === \"JSON\"
-            || propKey === \"parseFloat\"
-            || propKey === \"parseInt\"
-            || propKey === \"NaN\"
-            || propKey === \"isNaN\"
-            || propKey === \"Infinity\"
-            || propKey === \"isFinite\"
-            || propKey === \"ImageData\"
-            || propKey === \"URL\"
-            || propKey === \"createImageBitmap\"
-            || propKey === \"setTimeout\"
-            || propKey === \"clearTimeout\"
-            || propKey === \"setInterval\"
-            || propKey === \"clearInterval\"
-            || propKey === \"TextEncoder\"
-            || propKey === \"TextDecoder\"
-        ) {return Reflect.get(...arguments);} return undefined;",
-    );
-    let apply =
-        js_sys::Function::new_with_args("target, thisArgument, argumentsList", "return undefined;");
-    let construct =
-        js_sys::Function::new_with_args("target, argumentsList, newTarget", "return undefined;");
-    let define_prop =
-        js_sys::Function::new_with_args("target, propKey, propDesc", "return undefined;");
-    let delete_prop = js_sys::Function::new_with_args("target, propKey", "return undefined;");
-    js_sys::Reflect::set(
-        &JsValue::from(&handler),
-        &JsValue::from_str("set"),
-        &JsValue::from(set),
-    )
-    .unwrap();
-    js_sys::Reflect::set(
-        &JsValue::from(&handler),
-        &JsValue::from_str("get"),
-        &JsValue::from(get),
-    )
-    .unwrap();
-    js_sys::Reflect::set(
-        &JsValue::from(&handler),
-        &JsValue::from_str("apply"),
-        &JsValue::from(apply),
-    )
-    .unwrap();
-    js_sys::Reflect::set(
-        &JsValue::from(&handler),
-        &JsValue::from_str("construct"),
-        &JsValue::from(construct),
-    )
-    .unwrap();
-    js_sys::Reflect::set(
-        &JsValue::from(&handler),
-        &JsValue::from_str("defineProperty"),
-        &JsValue::from(define_prop),
-    )
-    .unwrap();
-    js_sys::Reflect::set(
-        &JsValue::from(&handler),
-        &JsValue::from_str("deleteProperty"),
-        &JsValue::from(delete_prop),
-    )
-    .unwrap();
-    js_sys::Proxy::new(global, &handler)
-}
+    /// Binds `globalThis` and `document` to proxies exposing what the capability policy of
+    /// the realm allows, replacing the proxies of a previous policy, and binds `console`.
+    /// `console` is wrapped to record the messages of cells, `fetch` to apply the fetch policy
+    /// of the realm, and in deterministic mode, the sources of randomness and time are
+    /// replaced, `crypto` and `performance` even if the policy hides them.
+    pub fn insert_globals(&mut self) -> Result<(), Error> {
+        let global = js_sys::global();
+        let mut policy = CapabilityPolicy::clone(&self.realm.borrow().config.policy);
+        if let Some(globals) = crate::builtin::deterministic::globals(&self.realm) {
+            let hidden = js_sys::Reflect::get(&globals, &JsValue::from_str("virtual"))?;
+            for name in crate::builtin::deterministic::DETERMINISTIC_GLOBALS {
+                let value = if policy.exposes(name) {
+                    js_sys::Reflect::get(&globals, &JsValue::from_str(name))?
+                } else if crate::builtin::deterministic::VIRTUAL_GLOBALS.contains(name) {
+                    js_sys::Reflect::get(&hidden, &JsValue::from_str(name))?
+                } else {
+                    continue;
+                };
+                policy.wrap(name, value);
+            }
+        }
+        if policy.exposes("console") {
+            let console = crate::builtin::console::console_object(&self.realm, None, None);
+            policy.wrap("console", console);
+        }
+        if policy.exposes("fetch") {
+            let fetch = crate::builtin::fetch::fetch_function(&self.realm);
+            policy.wrap("fetch", JsValue::from(fetch));
+        }
+        let global_proxy = policy.global_proxy(&global);
+        let global_this = Rc::new(RefCell::new(Value::from(JsValue::from(global_proxy))));
+        self.insert("globalThis", global_this.clone())?;
+        self.insert("self", global_this.clone())?;
+        self.insert("window", global_this.clone())?;
+        self.insert("this", global_this)?;
+
+        let document_proxy = policy.document_proxy(&global)?;
+        let document = Rc::new(RefCell::new(Value::from(JsValue::from(document_proxy))));
+        self.insert("document", document)?;
+        self.bind_console(None, None);
+        Ok(())
+    }
+
+    /// Binds `console` to a console attributing messages to the cell `cell` in the run `run`,
+    /// unless the capability policy hides it or a cell declared its own `console`. Functions
+    /// keep the console of the cell they were created in, so messages written by callbacks
+    /// after the cell finished are still attributed to it.
+    pub fn bind_console(&mut self, cell: Option<&str>, run: Option<u32>) {
+        let declared = self.stack[0].get("console").is_some_and(|x| {
+            !crate::builtin::console::is_console_object(&self.realm, x.borrow().as_ref())
+        });
+        if declared {
+            return;
+        }
+        if self.realm.borrow().config.policy.exposes("console") {
+            let console = crate::builtin::console::console_object(&self.realm, cell, run);
+            self.stack[0].insert(
+                String::from("console"),
+                Rc::new(RefCell::new(Value::from(console))),
+            );
+        } else {
+            self.stack[0].remove("console");
+        }
+    }
 
-#[inline]
-fn create_document_proxy(global: &js_sys::Object) -> Result<js_sys::Proxy, Error> {
-    let document =
-        js_sys::Reflect::get(global, &JsValue::from_str("document")).map_err(|x| Error::from(x))?;
-    let handler = js_sys::Object::new();
-    let set =
-        js_sys::Function::new_with_args("target, propKey, value, receiver", "return undefined;");
-    let get = js_sys::Function::new_with_args(
-        "target, propKey, receiver",
-        "if (propKey === \"createElement\") {return Reflect.get(...arguments).bind(document);} return undefined;",
-    );
-    let apply =
-        js_sys::Function::new_with_args("target, thisArgument, argumentsList", "return undefined;");
-    let construct =
-        js_sys::Function::new_with_args("target, argumentsList, newTarget", "return undefined;");
-    let define_prop =
-        js_sys::Function::new_with_args("target, propKey, propDesc", "return undefined;");
-    let delete_prop = js_sys::Function::new_with_args("target, propKey", "return undefined;");
-    js_sys::Reflect::set(
-        &JsValue::from(&handler),
-        &JsValue::from_str("set"),
-        &JsValue::from(set),
-    )
-    .unwrap();
-    js_sys::Reflect::set(
-        &JsValue::from(&handler),
-        &JsValue::from_str("get"),
-        &JsValue::from(get),
-    )
-    .unwrap();
-    js_sys::Reflect::set(
-        &JsValue::from(&handler),
-        &JsValue::from_str("apply"),
-        &JsValue::from(apply),
-    )
-    .unwrap();
-    js_sys::Reflect::set(
-        &JsValue::from(&handler),
-        &JsValue::from_str("construct"),
-        &JsValue::from(construct),
-    )
-    .unwrap();
-    js_sys::Reflect::set(
-        &JsValue::from(&handler),
-        &JsValue::from_str("defineProperty"),
-        &JsValue::from(define_prop),
-    )
-    .unwrap();
-    js_sys::Reflect::set(
-        &JsValue::from(&handler),
-        &JsValue::from_str("deleteProperty"),
-        &JsValue::from(delete_prop),
-    )
-    .unwrap();
-    Ok(js_sys::Proxy::new(&document, &handler))
+    /// Binds `display` to a display attributing outputs to the cell `cell`, unless a cell
+    /// declared its own `display`. Like the console, functions keep the display of the cell
+    /// they were created in.
+    pub fn bind_display(&mut self, cell: Option<&str>) {
+        let declared = self.stack[0].get("display").is_some_and(|x| {
+            !crate::builtin::display::is_display_function(&self.realm, x.borrow().as_ref())
+        });
+        if declared {
+            return;
+        }
+        let display = crate::builtin::display::display_function(&self.realm, cell);
+        self.stack[0].insert(
+            String::from("display"),
+            Rc::new(RefCell::new(Value::from(JsValue::from(display)))),
+        );
+    }
 }
 
 impl Environments {
@@ -275,8 +248,38 @@ impl Environments {
         }
     }
 
+    /// Records that the cell `cell` defines the top-level `names`, taking them over from the
+    /// cells that defined them before.
+    pub fn own_bindings(&mut self, cell: &str, names: impl IntoIterator<Item = String>) {
+        for name in names {
+            let previous = self
+                .cell_bindings
+                .values_mut()
+                .find_map(|x| x.remove(&name))
+                .unwrap_or_else(|| self.stack[0].get(&name).cloned());
+            self.cell_bindings
+                .entry(String::from(cell))
+                .or_default()
+                .insert(name, previous);
+        }
+    }
+
+    /// Removes the top-level bindings of the cell `cell`, restoring the values they shadowed.
+    pub fn drop_cell(&mut self, cell: &str) {
+        for (name, previous) in self.cell_bindings.remove(cell).unwrap_or_default() {
+            match previous {
+                Some(value) => self.stack[0].insert(name, value),
+                None => self.stack[0].remove(&name),
+            };
+        }
+    }
+
     pub fn closure(&self) -> ClosedEnvironment {
         let mut env = ClosedEnvironment::new();
+        env.realm = self.realm.clone();
+        env.module = self.module.clone();
+        env.cell = self.cell.clone();
+        env.source = self.source;
         self.stack
             .iter()
             .for_each(|x| x.iter().for_each(|(k, v)| env.insert(k, v.clone())));
@@ -286,6 +289,42 @@ impl Environments {
     pub fn from_closed_env(env: ClosedEnvironment) -> Environments {
         Environments {
             stack: vec![env.bindings],
+            realm: env.realm,
+            module: env.module,
+            cell: env.cell,
+            source: env.source,
+            ..Default::default()
+        }
+    }
+
+    /// Enters a statement list declaring `names` with `let` and `const`, which are in their
+    /// temporal dead zone until their declarations are evaluated.
+    pub fn enter_lexical(&mut self, names: impl IntoIterator<Item = String>) {
+        self.lexical
+            .push(names.into_iter().map(|x| (x, false)).collect());
+    }
+
+    pub fn exit_lexical(&mut self) {
+        self.lexical.pop();
+    }
+
+    /// Ends the temporal dead zone of names of the innermost statement list.
+    pub fn initialize<'a>(&mut self, names: impl IntoIterator<Item = &'a String>) {
+        if let Some(lexical) = self.lexical.last_mut() {
+            for name in names {
+                lexical.insert(name.clone(), true);
+            }
+        }
+    }
+
+    /// Fails with a `ReferenceError` if `name` is accessed in its temporal dead zone.
+    pub fn check_initialized(&self, name: &str) -> Result<(), Error> {
+        match self.lexical.iter().rev().find_map(|x| x.get(name)) {
+            Some(false) => Err(Error::from(js_sys::ReferenceError::new(&format!(
+                "ReferenceError: Cannot access {:?} before initialization.",
+                name
+            )))),
+            _ => Ok(()),
         }
     }
 
@@ -301,12 +340,20 @@ impl Environments {
 #[derive(Debug, Clone)]
 pub struct ClosedEnvironment {
     bindings: HashMap<String, Rc<RefCell<Value>>>,
+    realm: RcRealm,
+    module: Option<String>,
+    cell: Option<String>,
+    source: u32,
 }
 
 impl ClosedEnvironment {
     pub fn new() -> ClosedEnvironment {
         ClosedEnvironment {
             bindings: HashMap::new(),
+            realm: RcRealm::default(),
+            module: None,
+            cell: None,
+            source: 0,
         }
     }
     pub fn insert(&mut self, key: &str, obj: Rc<RefCell<Value>>) {
@@ -336,4 +383,8 @@ impl ClosedEnvironment {
     pub fn bindings(&self) -> &HashMap<String, Rc<RefCell<Value>>> {
         &self.bindings
     }
+
+    pub fn realm(&self) -> &RcRealm {
+        &self.realm
+    }
 }
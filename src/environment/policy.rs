@@ -0,0 +1,258 @@
+use js_sys::{Error, Reflect};
+use std::collections::{BTreeMap, BTreeSet};
+use wasm_bindgen::prelude::*;
+
+/// Globals cells can use unless the host configures otherwise. DOM nodes created with
+/// `document.createElement` are output as sanitized copies, see `builtin::html::sanitize`.
+const DEFAULT_GLOBALS: &[&str] = &[
+    "console",
+    "undefined",
+    "fetch",
+    "Number",
+    "Boolean",
+    "Object",
+    "Function",
+    "String",
+    "Generator",
+    "Iterator",
+    "Symbol",
+    "Math",
+    "Date",
+    "RegExp",
+    "Array",
+    "Int8Array",
+    "Uint8Array",
+    "Uint8ClampedArray",
+    "Int16Array",
+    "Uint16Array",
+    "Int32Array",
+    "Uint32Array",
+    "Float32Array",
+    "Float64Array",
+    "BigInt64Array",
+    "BigUint64Array",
+    "Map",
+    "Set",
+    "WeakMap",
+    "WeakSet",
+    "ArrayBuffer",
+    "SharedArrayBuffer",
+    "Error",
+    "EvalError",
+    "RangeError",
+    "ReferenceError",
+    "SyntaxError",
+    "TypeError",
+    "DataView",
+    "Promise",
+    "JSON",
+    "parseFloat",
+    "parseInt",
+    "NaN",
+    "isNaN",
+    "Infinity",
+    "isFinite",
+    "ImageData",
+    "URL",
+    "createImageBitmap",
+    "setTimeout",
+    "clearTimeout",
+    "setInterval",
+    "clearInterval",
+    "TextEncoder",
+    "TextDecoder",
+    "document.createElement",
+];
+
+/// Methods of `document` that query the page without changing it.
+pub const DOCUMENT_QUERIES: &[&str] = &[
+    "document.getElementById",
+    "document.getElementsByClassName",
+    "document.getElementsByName",
+    "document.getElementsByTagName",
+    "document.querySelector",
+    "document.querySelectorAll",
+];
+
+/// Decides which host APIs cells can use. Capabilities are named after the global, e.g.
+/// `fetch`, or after the member of `document`, e.g. `document.createElement`.
+///
+/// The policy drives both the proxies of `globalThis` and `document` and the globals bound to
+/// functions compiled to JavaScript, so interpreted and compiled code see the same APIs.
+#[derive(Clone, Debug)]
+pub struct CapabilityPolicy {
+    allowed: BTreeSet<String>,
+    /// Values cells get instead of the host's API, e.g. a `fetch` restricted to some origins.
+    wrappers: BTreeMap<String, JsValue>,
+}
+
+impl Default for CapabilityPolicy {
+    fn default() -> CapabilityPolicy {
+        CapabilityPolicy {
+            allowed: DEFAULT_GLOBALS.iter().map(|x| String::from(*x)).collect(),
+            wrappers: BTreeMap::new(),
+        }
+    }
+}
+
+impl CapabilityPolicy {
+    /// Creates a policy from the default by applying the host's options, an object with the
+    /// optional properties `allow` and `deny`, arrays of capabilities, `wrappers`, an object
+    /// mapping capabilities to the values cells get instead, and `documentQueries`, which
+    /// allows the read-only query methods of `document`.
+    pub fn from_js(options: &JsValue) -> Result<CapabilityPolicy, Error> {
+        if !options.is_object() {
+            return Err(policy_error("The capability policy must be an object."));
+        }
+        let mut policy = CapabilityPolicy::default();
+        if Reflect::get(options, &JsValue::from_str("documentQueries"))?.is_truthy() {
+            DOCUMENT_QUERIES.iter().for_each(|x| policy.allow(x));
+        }
+        for name in names(options, "allow")? {
+            policy.allow(&name);
+        }
+        for name in names(options, "deny")? {
+            policy.deny(&name);
+        }
+        let wrappers = Reflect::get(options, &JsValue::from_str("wrappers"))?;
+        if wrappers.is_object() {
+            for entry in js_sys::Object::entries(&js_sys::Object::from(wrappers)).iter() {
+                let entry = js_sys::Array::from(&entry);
+                policy.wrap(&entry.get(0).as_string().unwrap_or_default(), entry.get(1));
+            }
+        } else if !wrappers.is_undefined() {
+            return Err(policy_error("\"wrappers\" must be an object."));
+        }
+        Ok(policy)
+    }
+
+    pub fn allow(&mut self, name: &str) {
+        self.allowed.insert(String::from(name));
+    }
+
+    /// Removes a capability, including its wrapper.
+    pub fn deny(&mut self, name: &str) {
+        self.allowed.remove(name);
+        self.wrappers.remove(name);
+    }
+
+    /// Gives cells `value` instead of the host's API `name`.
+    pub fn wrap(&mut self, name: &str, value: JsValue) {
+        self.wrappers.insert(String::from(name), value);
+    }
+
+    /// Returns the value cells get instead of the host's API `name`, if any.
+    pub fn wrapper(&self, name: &str) -> Option<&JsValue> {
+        self.wrappers.get(name)
+    }
+
+    /// Returns whether cells can use the capability `name`.
+    pub fn exposes(&self, name: &str) -> bool {
+        self.allowed.contains(name) || self.wrappers.contains_key(name)
+    }
+
+    /// Returns the proxy of `globalThis`, which returns the allowed globals and ignores writes.
+    pub fn global_proxy(&self, global: &js_sys::Object) -> js_sys::Proxy {
+        let get = js_sys::Function::new_with_args(
+            "allowed, wrappers, target, propKey, receiver",
+            "if (wrappers.has(propKey)) {return wrappers.get(propKey);}
+            if (allowed.has(propKey)) {return Reflect.get(target, propKey, receiver);}
+            return undefined;",
+        );
+        read_only_proxy(global, self.bind_get(get))
+    }
+
+    /// Returns the proxy of `document`, which returns the allowed members bound to the
+    /// document and ignores writes.
+    pub fn document_proxy(&self, global: &js_sys::Object) -> Result<js_sys::Proxy, Error> {
+        let document = Reflect::get(global, &JsValue::from_str("document"))?;
+        let get = js_sys::Function::new_with_args(
+            "allowed, wrappers, target, propKey, receiver",
+            "const name = \"document.\" + String(propKey);
+            if (wrappers.has(name)) {return wrappers.get(name);}
+            if (!allowed.has(name)) {return undefined;}
+            const value = Reflect.get(target, propKey);
+            return typeof value === \"function\" ? value.bind(target) : value;",
+        );
+        Ok(read_only_proxy(
+            &js_sys::Object::from(document),
+            self.bind_get(get),
+        ))
+    }
+
+    fn bind_get(&self, get: js_sys::Function) -> js_sys::Function {
+        let allowed = js_sys::Set::new(&JsValue::undefined());
+        self.allowed.iter().for_each(|x| {
+            allowed.add(&JsValue::from_str(x));
+        });
+        let wrappers = js_sys::Map::new();
+        self.wrappers.iter().for_each(|(name, value)| {
+            wrappers.set(&JsValue::from_str(name), value);
+        });
+        get.bind2(&JsValue::null(), &allowed, &wrappers)
+    }
+}
+
+fn read_only_proxy(target: &js_sys::Object, get: js_sys::Function) -> js_sys::Proxy {
+    let handler = js_sys::Object::new();
+    let traps = [
+        (
+            "set",
+            js_sys::Function::new_with_args(
+                "target, propKey, value, receiver",
+                "return undefined;",
+            ),
+        ),
+        ("get", get),
+        (
+            "apply",
+            js_sys::Function::new_with_args(
+                "target, thisArgument, argumentsList",
+                "return undefined;",
+            ),
+        ),
+        (
+            "construct",
+            js_sys::Function::new_with_args(
+                "target, argumentsList, newTarget",
+                "return undefined;",
+            ),
+        ),
+        (
+            "defineProperty",
+            js_sys::Function::new_with_args("target, propKey, propDesc", "return undefined;"),
+        ),
+        (
+            "deleteProperty",
+            js_sys::Function::new_with_args("target, propKey", "return undefined;"),
+        ),
+    ];
+    for (name, trap) in traps {
+        Reflect::set(&handler, &JsValue::from_str(name), &trap).unwrap();
+    }
+    js_sys::Proxy::new(target, &handler)
+}
+
+fn names(options: &JsValue, key: &str) -> Result<Vec<String>, Error> {
+    let names = Reflect::get(options, &JsValue::from_str(key))?;
+    if names.is_undefined() {
+        return Ok(Vec::new());
+    }
+    if !js_sys::Array::is_array(&names) {
+        return Err(policy_error(&format!("{:?} must be an array.", key)));
+    }
+    js_sys::Array::from(&names)
+        .iter()
+        .map(|x| {
+            x.as_string()
+                .ok_or_else(|| policy_error(&format!("The entries of {:?} must be strings.", key)))
+        })
+        .collect()
+}
+
+fn policy_error(message: &str) -> Error {
+    Error::from(js_sys::TypeError::new(&format!(
+        "TypeError: Invalid capability policy: {}",
+        message
+    )))
+}
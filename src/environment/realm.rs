@@ -0,0 +1,124 @@
+use super::{CapabilityPolicy, Interrupt};
+use crate::builtin::console::ConsoleEntry;
+use crate::builtin::deterministic::DeterministicMode;
+use crate::builtin::fetch::{FetchPolicy, RequestRecord};
+use crate::display::{inspect::Handles, TableLimits};
+use crate::evaluator::modules::{ImportRecord, LocalModule};
+use crate::js::{EsmSh, ImportMap, ModuleCache, ModuleResolver};
+use js_sys::Object;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// State shared by all environments of one interpreter, including the environments
+/// of function calls.
+#[derive(Debug, Default)]
+pub struct Realm {
+    /// Namespaces of cells that published values with `export`, keyed by cell name.
+    pub modules: HashMap<String, Object>,
+    /// Modules imported while the host's module loader is set, keyed by URL.
+    pub local_modules: HashMap<String, LocalModule>,
+    /// Pairs of the URLs of a module being evaluated and of a module it waits for.
+    pub module_waits: Vec<(String, String)>,
+    /// `module` objects of CommonJS modules loaded by `require`, keyed by URL.
+    pub commonjs_modules: HashMap<String, Object>,
+    /// The `require` builtin of cells.
+    pub require: Option<js_sys::Function>,
+    /// The `require` builtins of cells and of CommonJS modules, which calls in interpreted code
+    /// are recognized by.
+    pub requires: Option<js_sys::WeakSet>,
+    /// Imports of cells with their outcome, keyed by cell id.
+    pub imports: HashMap<String, Vec<ImportRecord>>,
+    /// The `fetch` of cells, which applies the fetch policy.
+    pub fetch: Option<js_sys::Function>,
+    /// Requests made with `fetch`, keyed by the id of the cell that made them.
+    pub requests: HashMap<String, Vec<RequestRecord>>,
+    /// Object with the builtins `html`, `svg` and `element` and the sanitizers of outputs.
+    pub output_builders: Option<Object>,
+    /// Function rendering values as MIME bundles.
+    pub renderer: Option<js_sys::Function>,
+    /// Object with the functions previewing and inspecting values.
+    pub inspector: Option<Object>,
+    /// Values of cell results the host can inspect.
+    pub handles: Handles,
+    /// Replacements of the globals that make cells nondeterministic, in deterministic mode.
+    pub deterministic: Option<Object>,
+    /// Id of the cell being evaluated, `None` between cells and for cells without id.
+    pub cell: Option<String>,
+    /// Factory of the consoles of cells, which record their messages.
+    pub console: Option<Object>,
+    /// Messages of the cell being evaluated, `None` between cells.
+    pub console_output: Option<Vec<ConsoleEntry>>,
+    /// Number of the current run of a cell, which tells the messages of the cell being
+    /// evaluated from late messages of previous runs.
+    pub console_run: u32,
+    /// The `display` builtin attributing outputs to the cell being evaluated, whose `update`
+    /// the displays of cells share.
+    pub display: Option<js_sys::Function>,
+    /// Ids of the outputs shown with `display`, with the id of the cell that showed them. They
+    /// are dropped when the cell runs again, those of cells without id when the next cell
+    /// without id runs.
+    pub displays: HashMap<String, Option<String>>,
+    /// Number of outputs shown without an id, which their generated ids are numbered by.
+    pub display_count: u32,
+    /// Promise of the last output passed to the display handler, which the next waits for.
+    pub display_queue: Option<js_sys::Promise>,
+    /// Whether the globals of the session must be bound again after the running cell, because
+    /// the host changed the policies they depend on while it ran.
+    pub stale_globals: bool,
+    /// Interruption of the running cell, which the session shares.
+    pub interrupt: Interrupt,
+    /// Number of the sources parsed, e.g. of cells and modules.
+    pub sources: u32,
+    /// Template objects of tagged templates, created once per call site and reused on every
+    /// evaluation. Call sites are identified by the number of their source and their span.
+    pub template_objects: HashMap<(u32, u32, u32), js_sys::Array>,
+    pub config: Config,
+}
+
+pub type RcRealm = Rc<RefCell<Realm>>;
+
+/// Configuration of the host, which is kept when the environments are reset.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Resolves the specifiers of imports to URLs.
+    pub resolver: Rc<dyn ModuleResolver>,
+    /// Import map consulted before the resolver.
+    pub import_map: Rc<ImportMap>,
+    /// Cache imported modules are loaded through, if enabled by the host.
+    pub module_cache: Option<Rc<ModuleCache>>,
+    /// Host function returning the source of a module URL, which is then interpreted.
+    pub loader: Option<js_sys::Function>,
+    /// Host APIs cells can use.
+    pub policy: Rc<CapabilityPolicy>,
+    /// Restrictions of the `fetch` of cells.
+    pub fetch: Rc<FetchPolicy>,
+    /// Whether DOM outputs are used without sanitizing them, for notebooks the host trusts.
+    pub trust_output: bool,
+    /// Seed and start time of the deterministic mode, `None` if it is disabled.
+    pub deterministic: Option<DeterministicMode>,
+    /// Limits of the tables values are rendered as.
+    pub table_limits: TableLimits,
+    /// Host function receiving the messages of cells as they are written.
+    pub console_handler: Option<js_sys::Function>,
+    /// Host function receiving the outputs cells show with `display`.
+    pub display_handler: Option<js_sys::Function>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            resolver: Rc::new(EsmSh),
+            import_map: Rc::new(ImportMap::default()),
+            module_cache: None,
+            loader: None,
+            policy: Rc::new(CapabilityPolicy::default()),
+            fetch: Rc::new(FetchPolicy::default()),
+            trust_output: false,
+            deterministic: None,
+            table_limits: TableLimits::default(),
+            console_handler: None,
+            display_handler: None,
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use crate::environment::{Export, RcRealm};
+use crate::evaluator::modules::{self, LocalModule, LOADER_ROOT};
+use crate::evaluator::*;
+use crate::js;
+
+use futures::future::{FutureExt, LocalBoxFuture};
+use js_sys::Reflect;
+
+/// Returns the `require` builtin of the cells of a realm, creating it on first use.
+pub(crate) fn require_function(realm: &RcRealm) -> js_sys::Function {
+    let require = realm.borrow().require.clone();
+    require.unwrap_or_else(|| {
+        let require = sync_require(realm, None);
+        realm.borrow_mut().require = Some(require.clone());
+        require
+    })
+}
+
+/// Returns whether a function is a `require` builtin. Calls of it in interpreted code load the
+/// module before returning it.
+pub(crate) fn is_require(function: &RcValue, envs: &Environments) -> bool {
+    match (&*function.borrow(), &envs.realm.borrow().requires) {
+        (Value::JsFunction(function), Some(requires)) => requires.has(function),
+        _ => false,
+    }
+}
+
+/// Evaluates a call of `require`, which fetches the module and its dependencies if they
+/// are not loaded yet.
+pub(crate) async fn eval_require(
+    args: Vec<RcValue>,
+    envs: &mut Environments,
+) -> Result<RcValue, Error> {
+    let specifier = args
+        .first()
+        .and_then(|x| x.borrow().as_ref().as_string())
+        .ok_or_else(|| {
+            Error::from(js_sys::TypeError::new(
+                "TypeError: require() expects a module specifier.",
+            ))
+        })?;
+    let exports = require_module(specifier, envs.module.clone(), envs.realm.clone()).await?;
+    Ok(Value::from(exports).into())
+}
+
+/// Loads a module for `require` and returns its exports. Sources provided by the host's
+/// module loader and fetched modules are interpreted with the CommonJS wrapper. ES modules are
+/// returned as namespace.
+fn require_module(
+    specifier: String,
+    referrer: Option<String>,
+    realm: RcRealm,
+) -> LocalBoxFuture<'static, Result<JsValue, Error>> {
+    async move {
+        if specifier.starts_with("cell:") {
+            return modules::import_module(&specifier, None, &realm).await;
+        }
+        let url = resolve(&specifier, referrer.as_deref(), &realm)?;
+        if let Some(exports) = loaded_exports(&url, &realm)? {
+            return Ok(exports);
+        }
+        let loader = realm.borrow().config.loader.clone();
+        let source = match &loader {
+            Some(loader) => js::load_source(loader, &url).await?,
+            None => None,
+        };
+        match source {
+            Some(source) => {
+                let module = modules::parse_source(&url, source)?;
+                if modules::is_es_module(&module) {
+                    let evaluate = modules::eval_local_module(url.clone(), module, realm.clone());
+                    modules::local_module(&url, referrer.as_deref(), &realm, evaluate).await
+                } else {
+                    let module = eval_commonjs_module(url, module.body, realm).await?;
+                    Ok(Reflect::get(&module, &JsValue::from_str("exports"))?)
+                }
+            }
+            None if url.starts_with(LOADER_ROOT) => Err(Error::from(js_sys::TypeError::new(
+                &format!("TypeError: The module loader has no module {:?}.", url),
+            ))),
+            None => {
+                let (location, source) = js::fetch_commonjs(&url).await?;
+                let loaded = realm.borrow().commonjs_modules.get(&location).cloned();
+                let module = match loaded {
+                    Some(module) => module,
+                    None => eval_fetched_module(location, source, realm.clone()).await?,
+                };
+                realm
+                    .borrow_mut()
+                    .commonjs_modules
+                    .insert(url, module.clone());
+                Ok(Reflect::get(&module, &JsValue::from_str("exports"))?)
+            }
+        }
+    }
+    .boxed_local()
+}
+
+/// Interprets a CommonJS module with the bindings `module`, `exports`, `require`, `__filename`
+/// and `__dirname`, and returns its `module` object. Its `require` resolves against its URL,
+/// also when native code calls it.
+pub(crate) fn eval_commonjs_module(
+    url: String,
+    body: Vec<ModuleItem>,
+    realm: RcRealm,
+) -> LocalBoxFuture<'static, Result<js_sys::Object, Error>> {
+    async move {
+        let (module, exports) = new_module(&url)?;
+        realm
+            .borrow_mut()
+            .commonjs_modules
+            .insert(url.clone(), module.clone());
+        let mut envs = Environments::with_realm(realm.clone());
+        envs.module = Some(url.clone());
+        envs.insert("module", Value::from(JsValue::from(&module)).into())?;
+        envs.insert("exports", Value::from(JsValue::from(exports)).into())?;
+        let require = sync_require(&realm, Some(url.clone()));
+        envs.insert("require", Value::from(JsValue::from(require)).into())?;
+        envs.insert("__filename", Value::from(JsValue::from_str(&url)).into())?;
+        envs.insert(
+            "__dirname",
+            Value::from(JsValue::from_str(dirname(&url))).into(),
+        )?;
+        match eval_module(body, &mut envs).await {
+            Ok(_) => Ok(module),
+            Err(err) => {
+                realm.borrow_mut().commonjs_modules.remove(&url);
+                Err(err)
+            }
+        }
+    }
+    .boxed_local()
+}
+
+/// Evaluates a fetched module for `require`. JSON files are parsed and ES modules are imported
+/// like with `import`, through the import map and the module cache. CommonJS modules are
+/// interpreted, so that the capability and fetch policies apply to them.
+async fn eval_fetched_module(
+    url: String,
+    source: String,
+    realm: RcRealm,
+) -> Result<js_sys::Object, Error> {
+    if url.ends_with(".json") {
+        let (module, _) = new_module(&url)?;
+        Reflect::set(
+            &module,
+            &JsValue::from_str("exports"),
+            &js_sys::JSON::parse(&source)?,
+        )?;
+        return Ok(module);
+    }
+    let parsed = modules::parse_source(&url, source)?;
+    if modules::is_es_module(&parsed) {
+        let (module, _) = new_module(&url)?;
+        let namespace = modules::import_module(&url, None, &realm).await?;
+        Reflect::set(&module, &JsValue::from_str("exports"), &namespace)?;
+        return Ok(module);
+    }
+    eval_commonjs_module(url, parsed.body, realm).await
+}
+
+/// Imports a module that failed to import natively as CommonJS, e.g. a file of a package as
+/// published to npm, and returns its namespace. `None` if the module is no CommonJS module.
+pub(crate) async fn import_commonjs(
+    url: &str,
+    realm: &RcRealm,
+) -> Result<Option<js_sys::Object>, Error> {
+    let loaded = realm.borrow().commonjs_modules.get(url).cloned();
+    let module = match loaded {
+        Some(module) => module,
+        None => {
+            let (location, source) = js::fetch_commonjs(url).await?;
+            let parsed = modules::parse_source(&location, source)?;
+            if modules::is_es_module(&parsed) {
+                return Ok(None);
+            }
+            let module = eval_commonjs_module(location, parsed.body, realm.clone()).await?;
+            realm
+                .borrow_mut()
+                .commonjs_modules
+                .insert(String::from(url), module.clone());
+            module
+        }
+    };
+    commonjs_namespace(&module).map(Some)
+}
+
+/// Creates a `require` function resolving against `referrer`, which returns the modules that
+/// are already loaded when native code calls it.
+fn sync_require(realm: &RcRealm, referrer: Option<String>) -> js_sys::Function {
+    let weak = Rc::downgrade(realm);
+    let require: js_sys::Function = Closure::wrap(Box::new(
+        move |specifier: JsValue| -> Result<JsValue, JsValue> {
+            let realm = weak.upgrade().ok_or_else(|| {
+                Error::new("ERROR: The interpreter of this module has been reset.")
+            })?;
+            let specifier = specifier.as_string().ok_or_else(|| {
+                Error::from(js_sys::TypeError::new(
+                    "TypeError: require() expects a module specifier.",
+                ))
+            })?;
+            if let Some(name) = specifier.strip_prefix("cell:") {
+                if let Some(namespace) = realm.borrow().modules.get(name) {
+                    return Ok(JsValue::from(namespace));
+                }
+            }
+            let url = resolve(&specifier, referrer.as_deref(), &realm)?;
+            loaded_exports(&url, &realm)?.ok_or_else(|| {
+                JsValue::from(Error::new(&format!(
+                    "Error: Module {:?} is not loaded. Call require({:?}) in a cell to load it.",
+                    specifier, specifier
+                )))
+            })
+        },
+    )
+        as Box<dyn FnMut(JsValue) -> Result<JsValue, JsValue>>)
+    .into_js_value()
+    .unchecked_into();
+    realm
+        .borrow_mut()
+        .requires
+        .get_or_insert_with(js_sys::WeakSet::new)
+        .add(&require);
+    require
+}
+
+/// Returns the namespace of a CommonJS module for `import`, with `module.exports` as default
+/// export and its own enumerable properties as named exports.
+pub(crate) fn commonjs_namespace(module: &js_sys::Object) -> Result<js_sys::Object, Error> {
+    let exports = Reflect::get(module, &JsValue::from_str("exports"))?;
+    let mut envs = Environments::empty();
+    if exports.is_object() {
+        for key in js_sys::Object::keys(&js_sys::Object::from(exports.clone())).iter() {
+            let name = key.as_string().unwrap_or_default();
+            if name != "default" {
+                let value = Reflect::get(&exports, &key)?;
+                envs.exports
+                    .push((name, Export::Value(Value::from(value).into())));
+            }
+        }
+    }
+    envs.exports.push((
+        String::from("default"),
+        Export::Value(Value::from(exports).into()),
+    ));
+    modules::module_namespace(&mut envs)
+}
+
+fn resolve(specifier: &str, referrer: Option<&str>, realm: &RcRealm) -> Result<String, Error> {
+    let config = realm.borrow().config.clone();
+    let referrer = match (&config.loader, referrer) {
+        (Some(_), None) => Some(LOADER_ROOT),
+        (_, referrer) => referrer,
+    };
+    js::resolve_commonjs(specifier, referrer, &config.import_map, &*config.resolver)
+}
+
+/// Returns the exports of a module that `require` or `import` have loaded already.
+fn loaded_exports(url: &str, realm: &RcRealm) -> Result<Option<JsValue>, Error> {
+    let realm = realm.borrow();
+    if let Some(module) = realm.commonjs_modules.get(url) {
+        Ok(Some(Reflect::get(module, &JsValue::from_str("exports"))?))
+    } else if let Some(LocalModule::Evaluated(namespace)) = realm.local_modules.get(url) {
+        Ok(Some(JsValue::from(namespace)))
+    } else {
+        Ok(None)
+    }
+}
+
+fn new_module(url: &str) -> Result<(js_sys::Object, js_sys::Object), Error> {
+    let module = js_sys::Object::new();
+    let exports = js_sys::Object::new();
+    Reflect::set(&module, &JsValue::from_str("exports"), &exports)?;
+    Reflect::set(&module, &JsValue::from_str("id"), &JsValue::from_str(url))?;
+    Ok((module, exports))
+}
+
+fn dirname(url: &str) -> &str {
+    url.rfind('/').map(|x| &url[..x]).unwrap_or(url)
+}
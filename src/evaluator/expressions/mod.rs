@@ -5,9 +5,9 @@ use crate::evaluator::*;
 use futures::future::{FutureExt, LocalBoxFuture};
 use futures::stream::{self, StreamExt};
 use js_sys::Error;
-use wasm_bindgen_futures::JsFuture;
 
 mod binary;
+mod template;
 pub mod unary;
 
 pub(crate) fn eval_expr<'a>(
@@ -22,6 +22,10 @@ pub(crate) fn eval_expr<'a>(
                 Lit::Bool(boolean) => Ok(Value::Bool(JsBool::from(boolean.value)).into()),
                 Lit::Str(stringlit) => Ok(Value::String(JsString::from(&*stringlit.value)).into()),
                 Lit::Null(_) => Ok(Value::Null(JsValue::null()).into()),
+                Lit::BigInt(bigint) => Ok(Value::BigInt(JsBigInt::new(&JsValue::from_str(
+                    &bigint.value.to_string(),
+                ))?)
+                .into()),
                 Lit::Regex(regex) => Ok(Value::Object(js_sys::Object::from(js_sys::RegExp::new(
                     &regex.exp,
                     &regex.flags,
@@ -68,12 +72,24 @@ pub(crate) fn eval_expr<'a>(
                     .0;
                 Ok(Value::Object(JsObject::from(array)).into())
             }
-            Expr::Ident(ident) => Ok(envs.get(&ident.sym)?),
-            Expr::Unary(unary) => Ok(unary::eval_unary_expression(
-                unary.op,
-                &eval_expr(*unary.arg, envs).await?.borrow(),
-            )?
-            .into()),
+            Expr::Ident(ident) => {
+                envs.check_initialized(&ident.sym)?;
+                Ok(envs.get(&ident.sym)?)
+            }
+            Expr::Unary(unary) => match (unary.op, *unary.arg) {
+                (UnaryOp::Delete, arg) => objects::eval_delete_expr(arg, envs).await,
+                // Unresolvable references are read from `globalThis` as `undefined`, only names
+                // in their temporal dead zone fail.
+                (UnaryOp::TypeOf, Expr::Ident(ident)) => {
+                    envs.check_initialized(&ident.sym)?;
+                    Ok(unary::eval_typeof_operator(&envs.get(&ident.sym)?.borrow())?.into())
+                }
+                (op, arg) => Ok(unary::eval_unary_expression(
+                    op,
+                    &eval_expr(arg, envs).await?.borrow(),
+                )?
+                .into()),
+            },
             Expr::Bin(binary) => Ok(binary::eval_binary_expression(
                 binary.op,
                 &eval_expr(*binary.left, envs).await?.borrow(),
@@ -100,28 +116,33 @@ pub(crate) fn eval_expr<'a>(
                         funexp.function.body
                     )))),
                 }?;
-                functions::function_declaration(
+                let function = functions::function_declaration(
                     funexp.function.params.into_iter().map(|x| x.pat).collect(),
                     body,
                     funexp.function.is_async,
                     envs,
                     None,
                 )
-                .await
-                .map(|x| x.into())
+                .await?;
+                if let Some(ident) = funexp.ident {
+                    functions::set_function_name(function.as_ref(), &ident.sym)?;
+                }
+                Ok(function.into())
             }
             Expr::Call(call) => functions::call_function(call, envs).await,
             Expr::Object(objlit) => objects::eval_obj_lit_expr(objlit, envs).await,
             Expr::Member(memexpr) => objects::eval_member_expr(memexpr, envs).await,
             Expr::New(newexpr) => objects::eval_new_expr(newexpr, envs).await,
             Expr::Paren(parexpr) => eval_expr(*parexpr.expr, envs).await,
-            Expr::Await(awaitexpr) => match &*eval_expr(*awaitexpr.arg, envs).await?.borrow() {
-                Value::Object(obj) => Ok(Value::from(
-                    JsFuture::from(js_sys::Promise::from(JsValue::from(obj))).await?,
-                )
-                .into()),
-                _ => Ok(Value::Undefined(JsValue::undefined()).into()),
-            },
+            Expr::Await(awaitexpr) => {
+                let value = eval_expr(*awaitexpr.arg, envs).await?;
+                let promise = match &*value.borrow() {
+                    Value::Object(obj) => js_sys::Promise::from(JsValue::from(obj)),
+                    _ => return Ok(Value::Undefined(JsValue::undefined()).into()),
+                };
+                let interrupt = envs.realm.borrow().interrupt.clone();
+                Ok(Value::from(interrupt.wait(promise).await?).into())
+            }
             Expr::Assign(assignexpr) => expressions::eval_assign_expr(assignexpr, envs).await,
             Expr::Update(update_expr) => {
                 expressions::eval_update_expression(update_expr, envs).await
@@ -150,40 +171,15 @@ pub(crate) fn eval_expr<'a>(
                     eval_expr(*cond.alt, envs).await
                 }
             }
-            Expr::Tpl(template) => {
-                let last = template.quasis[template.quasis.len() - 1].clone();
-                let quasis_iter = stream::iter(template.quasis);
-                let str = stream::iter(template.exprs)
-                    .zip(quasis_iter)
-                    .fold(
-                        Ok::<_, Error>((JsString::from(JsValue::from_str("")), envs)),
-                        |acc, x| async move {
-                            let (string, envs) = acc?;
-                            let expr = eval_expr(*x.0, envs).await?;
-                            let next = JsString::from(JsValue::from_str(&match x.1.cooked {
-                                Some(y) => y,
-                                None => x.1.raw,
-                            }))
-                            .concat(expr.borrow().as_ref());
-                            let new = string.concat(&next);
-                            Ok((new, envs))
-                        },
-                    )
-                    .await?
-                    .0;
-                let str = if last.tail {
-                    str.concat(&JsString::from(JsValue::from_str(match &last.cooked {
-                        Some(y) => &y,
-                        None => &last.raw,
-                    })))
-                } else {
-                    str
-                };
-                Ok(Value::String(str).into())
+            Expr::Tpl(template) => template::eval_tpl(template, envs).await,
+            Expr::TaggedTpl(tagged) => template::eval_tagged_tpl(tagged, envs).await,
+            Expr::Class(class_expr) => {
+                let class = class::eval_class(*class_expr.class, envs).await?;
+                if let Some(ident) = class_expr.ident {
+                    functions::set_function_name(&class, &ident.sym)?;
+                }
+                Ok(Value::JsFunction(class).into())
             }
-            Expr::Class(class_expr) => class::eval_class(*class_expr.class, envs)
-                .await
-                .map(|x| Value::JsFunction(x).into()),
             _ => Err(Error::new(&format!(
                 "ERROR: Expression {:?} is not supported.",
                 expr
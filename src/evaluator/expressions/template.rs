@@ -0,0 +1,107 @@
+use crate::environment::Environments;
+use crate::evaluator::*;
+
+use futures::stream::{self, StreamExt};
+use js_sys::Error;
+
+use super::eval_expr;
+
+#[inline]
+pub(crate) async fn eval_tpl(template: Tpl, envs: &mut Environments) -> Result<RcValue, Error> {
+    let mut quasis = template.quasis.into_iter();
+    let head = quasis
+        .next()
+        .ok_or(Error::new("ERROR: Template literal without strings."))?;
+    let head = JsString::from(match head.cooked {
+        Some(y) => y.to_string(),
+        None => head.raw.to_string(),
+    });
+    let str = stream::iter(template.exprs.into_iter().zip(quasis))
+        .fold(Ok::<_, Error>((head, envs)), |acc, x| async move {
+            let (string, envs) = acc?;
+            let expr = eval_expr(*x.0, envs).await?;
+            let substitution = expr.borrow().to_js_string()?;
+            let next = JsString::from(match x.1.cooked {
+                Some(y) => y.to_string(),
+                None => x.1.raw.to_string(),
+            });
+            Ok((string.concat(&substitution).concat(&next), envs))
+        })
+        .await?
+        .0;
+    Ok(Value::String(str).into())
+}
+
+#[inline]
+pub(crate) async fn eval_tagged_tpl(
+    tagged: TaggedTpl,
+    envs: &mut Environments,
+) -> Result<RcValue, Error> {
+    let (function, this) = match *tagged.tag {
+        Expr::Member(memexpr) => {
+            let this = eval_expr(*memexpr.obj.clone(), envs).await?;
+            (objects::get_member(&this, memexpr, envs).await?, this)
+        }
+        tag => (
+            eval_expr(tag, envs).await?,
+            Value::Undefined(JsValue::undefined()).into(),
+        ),
+    };
+    let strings = template_object(tagged.span, &tagged.tpl.quasis, envs)?;
+    let (args, _envs) = stream::iter(tagged.tpl.exprs)
+        .fold(
+            Ok::<_, Error>((vec![Value::Object(JsObject::from(strings)).into()], envs)),
+            |acc, x| async move {
+                let (mut args, envs) = acc?;
+                args.push(eval_expr(*x, envs).await?);
+                Ok((args, envs))
+            },
+        )
+        .await?;
+    functions::apply_function(function, this, args).await
+}
+
+/// Returns the frozen strings array with its frozen `raw` property for a tagged template,
+/// the same array for every evaluation of the call site.
+fn template_object(
+    span: swc_common::Span,
+    quasis: &[TplElement],
+    envs: &Environments,
+) -> Result<js_sys::Array, Error> {
+    let key = (envs.source, span.lo.0, span.hi.0);
+    let cached = envs.realm.borrow().template_objects.get(&key).cloned();
+    if let Some(strings) = cached {
+        return Ok(strings);
+    }
+    let strings = quasis
+        .iter()
+        .map(|x| match &x.cooked {
+            Some(y) => JsValue::from_str(y),
+            None => JsValue::undefined(),
+        })
+        .collect::<js_sys::Array>();
+    let raw = quasis
+        .iter()
+        .map(|x| JsValue::from_str(&x.raw))
+        .collect::<js_sys::Array>();
+    js_sys::Object::freeze(&raw);
+    js_sys::Object::define_property(
+        &strings,
+        &JsValue::from_str("raw"),
+        &objects::create_object_from_entries(vec![
+            (
+                JsValue::from_str("configurable"),
+                &JsValue::from_bool(false),
+            ),
+            (JsValue::from_str("enumerable"), &JsValue::from_bool(false)),
+            (JsValue::from_str("writable"), &JsValue::from_bool(false)),
+            (JsValue::from_str("value"), &raw),
+        ])?,
+    );
+    js_sys::Object::freeze(&strings);
+    envs.realm
+        .borrow_mut()
+        .template_objects
+        .insert(key, strings.clone());
+    Ok(strings)
+}
@@ -11,6 +11,7 @@ pub fn eval_unary_expression(opr: UnaryOp, argument: &Value) -> Result<Value, Er
         UnaryOp::Plus => eval_plus_operator(argument),
         UnaryOp::TypeOf => eval_typeof_operator(argument),
         UnaryOp::Void => eval_void_operator(argument),
+        UnaryOp::Tilde => eval_bitwise_not_operator(argument),
         _ => Err(Error::new(&format!(
             "ERROR: Unary operator {:?} is not supported.",
             opr
@@ -38,6 +39,7 @@ pub fn eval_not_operator(argument: &Value) -> Result<Value, Error> {
 pub fn eval_minus_operator(argument: &Value) -> Result<Value, Error> {
     match argument {
         Value::Number(x) => Ok(Value::Number(JsNumber::from(-x.value_of()))),
+        Value::BigInt(x) => Ok(Value::BigInt(-x)),
         _ => Err(Error::from(js_sys::TypeError::new(&format!(
             "ERROR: Minus operator not defined for {:}.",
             argument
@@ -76,6 +78,38 @@ pub fn eval_typeof_operator(argument: &Value) -> Result<Value, Error> {
     }
 }
 
+#[inline]
+pub fn eval_bitwise_not_operator(argument: &Value) -> Result<Value, Error> {
+    match argument {
+        Value::BigInt(x) => Ok(Value::BigInt(!x)),
+        Value::Number(x) => Ok(Value::Number(JsNumber::from(!to_int32(x.value_of())))),
+        Value::Bool(x) => Ok(Value::Number(JsNumber::from(!(x.value_of() as i32)))),
+        Value::Null(_) => Ok(Value::Number(JsNumber::from(!0))),
+        Value::Undefined(_) => Ok(Value::Number(JsNumber::from(!0))),
+        Value::String(x) => Ok(Value::Number(JsNumber::from(!to_int32(
+            JsValue::from(x).unchecked_into_f64(),
+        )))),
+        // Objects are converted by their own methods, which can return a BigInt.
+        Value::Object(_) | Value::Function(_) | Value::JsFunction(_) => Ok(Value::from(
+            js_sys::Function::new_with_args("x", "return ~x;")
+                .call1(&JsValue::undefined(), &JsValue::from(argument))?,
+        )),
+        _ => Err(Error::from(js_sys::TypeError::new(&format!(
+            "TypeError: Bitwise not operator not defined for {:}.",
+            argument
+        )))),
+    }
+}
+
+/// Converts a number to a signed 32 bit integer following the ECMAScript ToInt32 operation.
+#[inline]
+pub(crate) fn to_int32(number: f64) -> i32 {
+    if !number.is_finite() {
+        return 0;
+    }
+    (number.trunc() % 4294967296.0) as i64 as u32 as i32
+}
+
 #[inline]
 pub fn eval_void_operator(argument: &Value) -> Result<Value, Error> {
     match argument {
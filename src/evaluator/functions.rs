@@ -7,6 +7,7 @@ use futures::stream::{self, StreamExt};
 use js_sys::Error;
 use regex::Regex;
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::rc::Rc;
 use swc_common::{BytePos, Span, SyntaxContext};
 use swc_ecma_ast::*;
@@ -30,6 +31,18 @@ pub async fn function_declaration(
     )))
 }
 
+/// Sets the `name` of a function created for a declaration or a named expression, which
+/// functions created from source are otherwise missing.
+pub(crate) fn set_function_name(function: &JsValue, name: &str) -> Result<(), Error> {
+    if function.is_function() {
+        let descriptor = js_sys::Object::new();
+        js_sys::Reflect::set(&descriptor, &"value".into(), &name.into())?;
+        js_sys::Reflect::set(&descriptor, &"configurable".into(), &true.into())?;
+        js_sys::Object::define_property(function.unchecked_ref(), &"name".into(), &descriptor);
+    }
+    Ok(())
+}
+
 #[inline]
 pub async fn args_to_string(
     args: Vec<Pat>,
@@ -105,7 +118,7 @@ pub async fn call_function<'env>(
         Callee::Expr(expr) => match *expr {
             Expr::Member(memexpr) => {
                 this = evaluator::expressions::eval_expr(*memexpr.obj.clone(), envs).await?;
-                evaluator::objects::eval_member_expr(memexpr, envs).await
+                evaluator::objects::get_member(&this, memexpr, envs).await
             }
             _ => evaluator::expressions::eval_expr(*expr, envs).await,
         },
@@ -113,13 +126,10 @@ pub async fn call_function<'env>(
             "ERROR: Function callee super {:?} is not supported.",
             call.callee
         ))),
-        Callee::Import(_) => Err(Error::new(&format!(
-            "ERROR: Dynamic import for {:?} is not supported.",
-            call.callee
-        ))),
+        Callee::Import(_) => return evaluator::modules::eval_dynamic_import(call.args, envs).await,
     }?;
     let len = call.args.len();
-    let (mut args, _envs) = stream::iter(call.args)
+    let (args, envs) = stream::iter(call.args)
         .fold(Ok((Vec::with_capacity(len), envs)), |acc, x| async move {
             let (mut vec, envs) = acc?;
             match x.spread {
@@ -142,8 +152,21 @@ pub async fn call_function<'env>(
             Ok::<(Vec<_>, &mut Environments), Error>((vec, envs))
         })
         .await?;
+    if evaluator::commonjs::is_require(&function, envs) {
+        return evaluator::commonjs::eval_require(args, envs).await;
+    }
+    apply_function(function, this, args).await
+}
+
+pub(crate) async fn apply_function(
+    function: RcValue,
+    this: RcValue,
+    mut args: Vec<RcValue>,
+) -> Result<RcValue, Error> {
     let result = match &*function.borrow() {
         Value::Function(func) => {
+            let interrupt = func.env.realm().borrow().interrupt.clone();
+            interrupt.check().await?;
             let mut func_env = Environments::from_closed_env(func.env.clone());
             func_env.push_env();
 
@@ -233,6 +256,9 @@ pub fn new_jsfunction(
         };
         func_body.push(ret);
     });
+    // Names the body declares itself shadow the outer bindings and cannot be parameters too.
+    let mut declared = HashSet::new();
+    crate::graph::lexical_names(body, &mut declared);
     let body = crate::transform::emit_js(func_body)?;
     let mut new_args: Vec<String> = args
         .iter()
@@ -243,13 +269,15 @@ pub fn new_jsfunction(
         })
         .collect();
     let env_bindings = env.bindings();
+    let policy = env.realm().borrow().config.policy.clone();
     let extra = get_variable_names(&body).and_then(|x| {
         let arr = x
             .into_iter()
             .filter(|y| {
                 !(new_args.iter().any(|z| y == z))
+                    && !declared.contains(y.split_at(7).1)
                     && (env_bindings.contains_key(y.split_at(7).1)
-                        || in_global_this(y.split_at(7).1))
+                        || policy.exposes(y.split_at(7).1))
             })
             .collect::<Vec<String>>();
         if arr.len() != 0 {
@@ -295,68 +323,3 @@ fn get_variable_names(input: &str) -> Option<Vec<String>> {
             x
         })
 }
-
-#[inline]
-fn in_global_this(input: &str) -> bool {
-    if input == "fetch"
-        || input == "console"
-        || input == "undefined"
-        || input == "Number"
-        || input == "Boolean"
-        || input == "Object"
-        || input == "Function"
-        || input == "String"
-        || input == "Generator"
-        || input == "Iterator"
-        || input == "Symbol"
-        || input == "Math"
-        || input == "Date"
-        || input == "RegExp"
-        || input == "Array"
-        || input == "Int8Array"
-        || input == "Uint8Array"
-        || input == "Uint8ClampedArray"
-        || input == "Int16Array"
-        || input == "Uint16Array"
-        || input == "Int32Array"
-        || input == "Uint32Array"
-        || input == "Float32Array"
-        || input == "Float64Array"
-        || input == "BigInt64Array"
-        || input == "BigUint64Array"
-        || input == "Promise"
-        || input == "Map"
-        || input == "Set"
-        || input == "WeakMap"
-        || input == "WeakSet"
-        || input == "ArrayBuffer"
-        || input == "SharedArrayBuffer"
-        || input == "Error"
-        || input == "EvalError"
-        || input == "RangeError"
-        || input == "ReferenceError"
-        || input == "SyntaxError"
-        || input == "TypeError"
-        || input == "DataView"
-        || input == "JSON"
-        || input == "parseFloat"
-        || input == "parseInt"
-        || input == "NaN"
-        || input == "isNaN"
-        || input == "Infinity"
-        || input == "isFinite"
-        || input == "ImageData"
-        || input == "URL"
-        || input == "createImageBitmap"
-        || input == "setTimeout"
-        || input == "clearTimeout"
-        || input == "setInterval"
-        || input == "clearInterval"
-        || input == "TextEncoder"
-        || input == "TextDecoder"
-    {
-        true
-    } else {
-        false
-    }
-}
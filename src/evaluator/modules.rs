@@ -0,0 +1,611 @@
+use crate::builtin::deterministic;
+use crate::environment::{Export, RcRealm};
+use crate::evaluator::*;
+use crate::js;
+
+use futures::future::{FutureExt, LocalBoxFuture};
+use js_sys::Reflect;
+use lazy_static::lazy_static;
+use regex::Regex;
+use swc_common::sync::Lrc;
+use swc_common::{FileName, SourceMap};
+use swc_ecma_parser::{lexer::Lexer, Parser, StringInput, Syntax};
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+#[inline]
+pub(crate) async fn eval_module_decl(
+    moddecl: ModuleDecl,
+    envs: &mut Environments,
+) -> Result<RcValue, Error> {
+    match moddecl {
+        ModuleDecl::Import(importdecl) => {
+            let module = import_from_cell(&importdecl.src.value, envs).await?;
+            // All bindings are resolved before any of them is created, so a missing export
+            // leaves the environment untouched.
+            let bindings = importdecl
+                .specifiers
+                .iter()
+                .map(|x| match x {
+                    ImportSpecifier::Namespace(specifier) => {
+                        Ok((specifier.local.sym.to_string(), module.clone()))
+                    }
+                    ImportSpecifier::Named(specifier) => {
+                        let name = match &specifier.imported {
+                            Some(imported) => export_name(imported),
+                            None => specifier.local.sym.to_string(),
+                        };
+                        Ok((
+                            specifier.local.sym.to_string(),
+                            get_export(&module, &name, &importdecl.src.value)?,
+                        ))
+                    }
+                    ImportSpecifier::Default(specifier) => Ok((
+                        specifier.local.sym.to_string(),
+                        get_export(&module, "default", &importdecl.src.value)?,
+                    )),
+                })
+                .collect::<Result<Vec<(String, JsValue)>, Error>>()?;
+            for (name, value) in bindings {
+                envs.insert(&name, Value::from(value).into())?;
+            }
+            Ok(Value::Undefined(JsValue::undefined()).into())
+        }
+        ModuleDecl::ExportDecl(export) => {
+            let mut names = Vec::new();
+            match &export.decl {
+                Decl::Var(var) => var
+                    .decls
+                    .iter()
+                    .for_each(|x| pat_names(&x.name, &mut names)),
+                Decl::Fn(decl) => names.push(decl.ident.sym.to_string()),
+                Decl::Class(decl) => names.push(decl.ident.sym.to_string()),
+                _ => (),
+            };
+            statements::decl::eval_decl(export.decl, envs).await?;
+            envs.exports
+                .extend(names.into_iter().map(|x| (x.clone(), Export::Binding(x))));
+            Ok(Value::Undefined(JsValue::undefined()).into())
+        }
+        ModuleDecl::ExportDefaultExpr(export) => {
+            let value = expressions::eval_expr(*export.expr, envs).await?;
+            envs.exports
+                .push((String::from("default"), Export::Value(value)));
+            Ok(Value::Undefined(JsValue::undefined()).into())
+        }
+        ModuleDecl::ExportDefaultDecl(export) => {
+            let (ident, value) = match export.decl {
+                DefaultDecl::Fn(fn_expr) => (
+                    fn_expr.ident.clone(),
+                    expressions::eval_expr(Expr::Fn(fn_expr), envs).await?,
+                ),
+                DefaultDecl::Class(class_expr) => (
+                    class_expr.ident.clone(),
+                    expressions::eval_expr(Expr::Class(class_expr), envs).await?,
+                ),
+                DefaultDecl::TsInterfaceDecl(_) => {
+                    return Err(Error::new(
+                        "ERROR: Typescript declarations are not supported.",
+                    ))
+                }
+            };
+            let export = match ident {
+                Some(ident) => {
+                    envs.insert(&ident.sym, value)?;
+                    Export::Binding(ident.sym.to_string())
+                }
+                None => Export::Value(value),
+            };
+            envs.exports.push((String::from("default"), export));
+            Ok(Value::Undefined(JsValue::undefined()).into())
+        }
+        ModuleDecl::ExportNamed(named) => {
+            let module = match &named.src {
+                Some(src) => Some(import_from_cell(&src.value, envs).await?),
+                None => None,
+            };
+            let source = named
+                .src
+                .as_ref()
+                .map(|x| x.value.to_string())
+                .unwrap_or_default();
+            for specifier in named.specifiers {
+                let export = match (specifier, &module) {
+                    (ExportSpecifier::Named(specifier), None) => {
+                        let local = export_name(&specifier.orig);
+                        let exported = specifier.exported.as_ref().map(export_name);
+                        (exported.unwrap_or(local.clone()), Export::Binding(local))
+                    }
+                    (ExportSpecifier::Named(specifier), Some(module)) => {
+                        let imported = export_name(&specifier.orig);
+                        let value = get_export(module, &imported, &source)?;
+                        let exported = specifier.exported.as_ref().map(export_name);
+                        (
+                            exported.unwrap_or(imported),
+                            Export::Value(Value::from(value).into()),
+                        )
+                    }
+                    (ExportSpecifier::Namespace(specifier), Some(module)) => (
+                        export_name(&specifier.name),
+                        Export::Value(Value::from(module.clone()).into()),
+                    ),
+                    (ExportSpecifier::Default(specifier), Some(module)) => {
+                        let value = get_export(module, "default", &source)?;
+                        (
+                            specifier.exported.sym.to_string(),
+                            Export::Value(Value::from(value).into()),
+                        )
+                    }
+                    (specifier, None) => {
+                        return Err(Error::from(js_sys::SyntaxError::new(&format!(
+                            "SyntaxError: Export {:?} requires a module specifier.",
+                            specifier
+                        ))))
+                    }
+                };
+                envs.exports.push(export);
+            }
+            Ok(Value::Undefined(JsValue::undefined()).into())
+        }
+        ModuleDecl::ExportAll(all) => {
+            let module = import_from_cell(&all.src.value, envs).await?;
+            for key in js_sys::Object::keys(&js_sys::Object::from(module.clone())).iter() {
+                let key = key.as_string().unwrap_or_default();
+                if key != "default" {
+                    let value = Reflect::get(&module, &JsValue::from_str(&key))?;
+                    envs.exports
+                        .push((key, Export::Value(Value::from(value).into())));
+                }
+            }
+            Ok(Value::Undefined(JsValue::undefined()).into())
+        }
+        _ => Err(Error::new(&format!(
+            "ERROR: Module declaration {:?} not supported.",
+            moddecl
+        ))),
+    }
+}
+
+/// Reads an export from a module namespace. Importing a name the module does not export is a
+/// `SyntaxError`, as for static imports in a browser.
+fn get_export(module: &JsValue, name: &str, specifier: &str) -> Result<JsValue, Error> {
+    let key = JsValue::from_str(name);
+    if module.is_object() && Reflect::has(module, &key)? {
+        Ok(Reflect::get(module, &key)?)
+    } else {
+        Err(Error::from(js_sys::SyntaxError::new(&format!(
+            "SyntaxError: The requested module {:?} does not provide an export named {:?}.",
+            specifier, name
+        ))))
+    }
+}
+
+/// Evaluates `import(specifier)` to a promise of the module namespace.
+pub(crate) async fn eval_dynamic_import(
+    args: Vec<ExprOrSpread>,
+    envs: &mut Environments,
+) -> Result<RcValue, Error> {
+    let specifier = match args.into_iter().next() {
+        Some(arg) => expressions::eval_expr(*arg.expr, envs).await?,
+        None => {
+            return Err(Error::from(js_sys::SyntaxError::new(
+                "SyntaxError: import() requires a module specifier.",
+            )))
+        }
+    };
+    let specifier = specifier.borrow().to_js_string();
+    let realm = envs.realm.clone();
+    let referrer = envs.module.clone();
+    let cell = envs.cell.clone();
+    let promise = wasm_bindgen_futures::future_to_promise(async move {
+        let specifier = String::from(specifier?);
+        import_recorded(&specifier, referrer.as_deref(), cell.as_deref(), &realm)
+            .await
+            .map_err(JsValue::from)
+    });
+    Ok(Value::Object(JsObject::from(promise)).into())
+}
+
+/// Loads a module imported from `referrer`, the URL of the importing module or `None` for
+/// cells. Specifiers of the form `cell:name` refer to the exports of the cell `name`, all other
+/// specifiers are resolved by `js::resolve`. If the host has set a module loader, the source it
+/// returns is interpreted, otherwise the module is imported natively.
+pub(crate) async fn import_module(
+    specifier: &str,
+    referrer: Option<&str>,
+    realm: &RcRealm,
+) -> Result<JsValue, Error> {
+    if let Some(name) = specifier.strip_prefix("cell:") {
+        let module = realm.borrow().modules.get(name).cloned();
+        return module.map(JsValue::from).ok_or_else(|| {
+            Error::from(js_sys::ReferenceError::new(&format!(
+                "ReferenceError: Cell {:?} does not export any values.",
+                name
+            )))
+        });
+    }
+    let config = realm.borrow().config.clone();
+    let url = resolve_specifier(specifier, referrer, realm)?;
+    let loader = match &config.loader {
+        Some(loader) => loader,
+        None => return import_native(&url, realm).await,
+    };
+    let evaluate = {
+        let (url, loader, realm) = (url.clone(), loader.clone(), realm.clone());
+        async move {
+            match js::load_source(&loader, &url).await? {
+                Some(source) => {
+                    let module = parse_source(&url, source)?;
+                    eval_local_module(url, module, realm).await
+                }
+                None if url.starts_with(LOADER_ROOT) => Err(Error::from(js_sys::TypeError::new(
+                    &format!("TypeError: The module loader has no module {:?}.", url),
+                ))),
+                None => import_native(&url, &realm).await,
+            }
+        }
+        .boxed_local()
+    };
+    local_module(&url, referrer, realm, evaluate).await
+}
+
+/// Imports a module natively. Modules that fail to import are imported as CommonJS if they are,
+/// with `module.exports` as default export.
+async fn import_native(url: &str, realm: &RcRealm) -> Result<JsValue, Error> {
+    let cache = realm.borrow().config.module_cache.clone();
+    let loaded = realm.borrow().commonjs_modules.get(url).cloned();
+    if let Some(module) = loaded {
+        return commonjs::commonjs_namespace(&module).map(JsValue::from);
+    }
+    match js::import(url, cache.as_deref()).await {
+        Ok(namespace) => Ok(namespace),
+        Err(err) => match commonjs::import_commonjs(url, realm).await {
+            Ok(Some(namespace)) => Ok(JsValue::from(namespace)),
+            _ => Err(err),
+        },
+    }
+}
+
+/// A module imported while the host's module loader is set.
+#[derive(Clone, Debug)]
+pub enum LocalModule {
+    /// The module is being evaluated, with the promise of its namespace.
+    Evaluating(js_sys::Promise),
+    /// The namespace of the evaluated module.
+    Evaluated(js_sys::Object),
+}
+
+/// Returns the namespace of the module `url` imported from `referrer`. The module is evaluated
+/// by `evaluate` on its first import, later imports wait for the same evaluation. Imports by the
+/// modules the module imports itself fail, as they would wait forever.
+pub(crate) async fn local_module(
+    url: &str,
+    referrer: Option<&str>,
+    realm: &RcRealm,
+    evaluate: LocalBoxFuture<'static, Result<JsValue, Error>>,
+) -> Result<JsValue, Error> {
+    let loaded = realm.borrow().local_modules.get(url).cloned();
+    let promise = match loaded {
+        Some(LocalModule::Evaluated(namespace)) => return Ok(JsValue::from(namespace)),
+        Some(LocalModule::Evaluating(promise)) => promise,
+        None => {
+            let (key, weak) = (String::from(url), Rc::downgrade(realm));
+            let promise = future_to_promise(async move {
+                let namespace = evaluate.await;
+                if let Some(realm) = weak.upgrade() {
+                    let mut realm = realm.borrow_mut();
+                    match &namespace {
+                        Ok(namespace) => realm.local_modules.insert(
+                            key,
+                            LocalModule::Evaluated(js_sys::Object::from(namespace.clone())),
+                        ),
+                        Err(_) => realm.local_modules.remove(&key),
+                    };
+                }
+                namespace.map_err(JsValue::from)
+            });
+            realm
+                .borrow_mut()
+                .local_modules
+                .insert(String::from(url), LocalModule::Evaluating(promise.clone()));
+            promise
+        }
+    };
+    let wait = referrer.map(|x| (String::from(x), String::from(url)));
+    if let Some((referrer, url)) = &wait {
+        if waits_for(&realm.borrow().module_waits, url, referrer) {
+            return Err(Error::from(js_sys::ReferenceError::new(&format!(
+                "ReferenceError: Module {:?} is imported while it is evaluated, by a module it imports.",
+                url
+            ))));
+        }
+        realm
+            .borrow_mut()
+            .module_waits
+            .push((referrer.clone(), url.clone()));
+    }
+    let namespace = JsFuture::from(promise).await;
+    if let Some(wait) = wait {
+        let mut realm = realm.borrow_mut();
+        if let Some(i) = realm.module_waits.iter().position(|x| *x == wait) {
+            realm.module_waits.remove(i);
+        }
+    }
+    namespace.map_err(Error::from)
+}
+
+/// Returns whether the module `from` is `to` or waits for it, directly or through other modules.
+fn waits_for(waits: &[(String, String)], from: &str, to: &str) -> bool {
+    from == to
+        || waits
+            .iter()
+            .any(|(x, y)| x == from && waits_for(waits, y, to))
+}
+
+/// Resolves the specifier of an import from `referrer` to the URL the module is loaded from.
+fn resolve_specifier(
+    specifier: &str,
+    referrer: Option<&str>,
+    realm: &RcRealm,
+) -> Result<String, Error> {
+    let config = realm.borrow().config.clone();
+    // Cells are located at the root of the file system of the loader.
+    let referrer = match (&config.loader, referrer) {
+        (Some(_), None) => Some(LOADER_ROOT),
+        (_, referrer) => referrer,
+    };
+    js::resolve(specifier, referrer, &config.import_map, &*config.resolver)
+}
+
+/// Outcome of an import of a cell, which the host shows in its dependency panel.
+#[derive(Clone, Debug)]
+pub struct ImportRecord {
+    pub specifier: String,
+    /// The URL the specifier resolved to, `None` if it could not be resolved.
+    pub url: Option<String>,
+    /// Message of the error the import failed with.
+    pub error: Option<String>,
+    /// HTTP status of the failed request of the module. `None` for native imports, whose
+    /// responses are not visible.
+    pub status: Option<u16>,
+    /// Time the import took in milliseconds.
+    pub duration: f64,
+    /// Names exported by the module.
+    pub exports: Vec<String>,
+}
+
+impl ImportRecord {
+    /// Returns the package version pinned in the URL, e.g. `7.8.5` for
+    /// `https://esm.sh/d3@7.8.5`.
+    pub fn version(&self) -> Option<String> {
+        lazy_static! {
+            static ref VERSION: Regex = Regex::new(r"@(v?[0-9][^/?#&]*)").unwrap();
+        }
+        VERSION
+            .captures(self.url.as_deref()?)
+            .and_then(|x| x.get(1))
+            .map(|x| String::from(x.as_str()))
+    }
+
+    pub fn to_js(&self) -> Result<JsValue, Error> {
+        let optional = |x: Option<&str>| x.map(JsValue::from_str).unwrap_or(JsValue::null());
+        let record = objects::create_object_from_entries(vec![
+            (
+                JsValue::from_str("specifier"),
+                &JsValue::from_str(&self.specifier),
+            ),
+            (JsValue::from_str("url"), &optional(self.url.as_deref())),
+            (
+                JsValue::from_str("version"),
+                &optional(self.version().as_deref()),
+            ),
+            (
+                JsValue::from_str("status"),
+                &JsValue::from_str(if self.error.is_some() {
+                    "failed"
+                } else {
+                    "loaded"
+                }),
+            ),
+            (JsValue::from_str("error"), &optional(self.error.as_deref())),
+            (
+                JsValue::from_str("httpStatus"),
+                &self
+                    .status
+                    .map(|x| JsValue::from_f64(x as f64))
+                    .unwrap_or(JsValue::null()),
+            ),
+            (
+                JsValue::from_str("duration"),
+                &JsValue::from_f64(self.duration),
+            ),
+            (
+                JsValue::from_str("exports"),
+                &self
+                    .exports
+                    .iter()
+                    .map(|x| JsValue::from_str(x))
+                    .collect::<js_sys::Array>(),
+            ),
+        ])?;
+        Ok(JsValue::from(record))
+    }
+}
+
+async fn import_from_cell(specifier: &str, envs: &Environments) -> Result<JsValue, Error> {
+    import_recorded(
+        specifier,
+        envs.module.as_deref(),
+        envs.cell.as_deref(),
+        &envs.realm,
+    )
+    .await
+}
+
+/// Loads a module like `import_module`. Imports of cells are recorded in the realm under the
+/// id of the cell, or `""` for cells without id.
+async fn import_recorded(
+    specifier: &str,
+    referrer: Option<&str>,
+    cell: Option<&str>,
+    realm: &RcRealm,
+) -> Result<JsValue, Error> {
+    if referrer.is_some() {
+        return import_module(specifier, referrer, realm).await;
+    }
+    let start = deterministic::now(realm);
+    let result = import_module(specifier, None, realm).await;
+    let duration = deterministic::now(realm) - start;
+    let url = if specifier.starts_with("cell:") {
+        Some(String::from(specifier))
+    } else {
+        resolve_specifier(specifier, None, realm).ok()
+    };
+    let (error, status, exports) = match &result {
+        Ok(module) if module.is_object() => {
+            let exports = js_sys::Object::keys(&js_sys::Object::from(module.clone()))
+                .iter()
+                .filter_map(|x| x.as_string())
+                .collect();
+            (None, None, exports)
+        }
+        Ok(_) => (None, None, Vec::new()),
+        Err(err) => (
+            Some(String::from(err.message())),
+            js::fetch_status(err),
+            Vec::new(),
+        ),
+    };
+    let record = ImportRecord {
+        specifier: String::from(specifier),
+        url,
+        error,
+        status,
+        duration,
+        exports,
+    };
+    realm
+        .borrow_mut()
+        .imports
+        .entry(String::from(cell.unwrap_or_default()))
+        .or_default()
+        .push(record);
+    result
+}
+
+/// URL of the cells in the file system of the host's module loader.
+pub(crate) const LOADER_ROOT: &str = "file:///";
+
+pub(crate) fn parse_source(url: &str, source: String) -> Result<Module, Error> {
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(FileName::Custom(String::from(url)), source);
+    let lexer = Lexer::new(
+        Syntax::Es(Default::default()),
+        Default::default(),
+        StringInput::from(&*fm),
+        None,
+    );
+    Parser::new_from(lexer).parse_module().map_err(|err| {
+        Error::from(js_sys::SyntaxError::new(&format!(
+            "SyntaxError: {} in module {:?}.",
+            err.kind().msg(),
+            url
+        )))
+    })
+}
+
+/// Returns whether a module uses `import` or `export` declarations. Sources without them are
+/// evaluated as CommonJS.
+pub(crate) fn is_es_module(module: &Module) -> bool {
+    module
+        .body
+        .iter()
+        .any(|x| matches!(x, ModuleItem::ModuleDecl(_)))
+}
+
+/// Interprets a module returned by the host's module loader in a new environment and returns
+/// its namespace. The namespace of a CommonJS module has `module.exports` as default export.
+pub(crate) fn eval_local_module(
+    url: String,
+    module: Module,
+    realm: RcRealm,
+) -> LocalBoxFuture<'static, Result<JsValue, Error>> {
+    async move {
+        let namespace = if is_es_module(&module) {
+            let mut envs = Environments::with_realm(realm.clone());
+            envs.module = Some(url.clone());
+            match eval_module(module.body, &mut envs).await {
+                Ok(_) => module_namespace(&mut envs),
+                Err(err) => Err(err),
+            }
+        } else {
+            let loaded = realm.borrow().commonjs_modules.get(&url).cloned();
+            let commonjs_module = match loaded {
+                Some(commonjs_module) => Ok(commonjs_module),
+                None => {
+                    commonjs::eval_commonjs_module(url.clone(), module.body, realm.clone()).await
+                }
+            };
+            commonjs_module.and_then(|x| commonjs::commonjs_namespace(&x))
+        };
+        namespace.map(JsValue::from)
+    }
+    .boxed_local()
+}
+
+/// Creates the namespace object of the evaluated module from its exports.
+pub(crate) fn module_namespace(envs: &mut Environments) -> Result<js_sys::Object, Error> {
+    let namespace = js_sys::Object::create(&js_sys::Object::from(JsValue::null()));
+    let mut exports = std::mem::take(&mut envs.exports);
+    exports.sort_by(|x, y| x.0.cmp(&y.0));
+    for (name, export) in exports {
+        let value = match export {
+            Export::Binding(local) => envs.get(&local)?,
+            Export::Value(value) => value,
+        };
+        js_sys::Object::define_property(
+            &namespace,
+            &JsValue::from_str(&name),
+            &objects::create_object_from_entries(vec![
+                (JsValue::from_str("enumerable"), &JsValue::from_bool(true)),
+                (JsValue::from_str("value"), value.borrow().as_ref()),
+            ])?,
+        );
+    }
+    js_sys::Object::define_property(
+        &namespace,
+        &js_sys::Symbol::to_string_tag(),
+        &objects::create_object_from_entries(vec![(
+            JsValue::from_str("value"),
+            &JsValue::from_str("Module"),
+        )])?,
+    );
+    Ok(js_sys::Object::freeze(&namespace))
+}
+
+#[inline]
+fn export_name(name: &ModuleExportName) -> String {
+    match name {
+        ModuleExportName::Ident(ident) => ident.sym.to_string(),
+        ModuleExportName::Str(str) => str.value.to_string(),
+    }
+}
+
+/// Collects the names of all bindings declared by a pattern.
+pub(crate) fn pat_names(pat: &Pat, names: &mut Vec<String>) {
+    match pat {
+        Pat::Ident(ident) => names.push(ident.id.sym.to_string()),
+        Pat::Array(array) => array
+            .elems
+            .iter()
+            .flatten()
+            .for_each(|x| pat_names(x, names)),
+        Pat::Rest(rest) => pat_names(&rest.arg, names),
+        Pat::Object(object) => object.props.iter().for_each(|x| match x {
+            ObjectPatProp::KeyValue(kv) => pat_names(&kv.value, names),
+            ObjectPatProp::Assign(assign) => names.push(assign.key.sym.to_string()),
+            ObjectPatProp::Rest(rest) => pat_names(&rest.arg, names),
+        }),
+        Pat::Assign(assign) => pat_names(&assign.left, names),
+        _ => (),
+    }
+}
@@ -256,10 +256,17 @@ pub async fn eval_member_expr(
     memexpr: MemberExpr,
     envs: &mut Environments,
 ) -> Result<RcValue, Error> {
-    match &*evaluator::expressions::eval_expr(*memexpr.obj.clone(), envs)
-        .await?
-        .borrow()
-    {
+    let obj = evaluator::expressions::eval_expr(*memexpr.obj.clone(), envs).await?;
+    get_member(&obj, memexpr, envs).await
+}
+
+/// Reads the property of a member expression from its already evaluated object.
+pub(crate) async fn get_member(
+    obj: &RcValue,
+    memexpr: MemberExpr,
+    envs: &mut Environments,
+) -> Result<RcValue, Error> {
+    match &*obj.borrow() {
         Value::Object(obj) => match memexpr.prop {
             MemberProp::Ident(ident) => {
                 match Reflect::get(obj.as_ref(), &JsValue::from(ident.sym.to_string()))
@@ -354,6 +361,48 @@ pub async fn assign_member_expr(
     }
 }
 
+#[inline]
+pub async fn eval_delete_expr(expr: Expr, envs: &mut Environments) -> Result<RcValue, Error> {
+    let mut expr = expr;
+    while let Expr::Paren(parexpr) = expr {
+        expr = *parexpr.expr;
+    }
+    match expr {
+        Expr::Member(memexpr) => {
+            let obj = evaluator::expressions::eval_expr(*memexpr.obj, envs).await?;
+            let prop = match memexpr.prop {
+                MemberProp::Ident(ident) => JsValue::from(ident.sym.to_string()),
+                MemberProp::Computed(computed) => JsValue::from(
+                    &*evaluator::expressions::eval_expr(*computed.expr, envs)
+                        .await?
+                        .borrow(),
+                ),
+                _ => {
+                    return Err(Error::new(
+                        "ERROR: Private member expression could not be evaluated.",
+                    ))
+                }
+            };
+            let deleted = match &*obj.borrow() {
+                Value::Object(x) => Reflect::delete_property(x, &prop)?,
+                Value::JsFunction(x) => Reflect::delete_property(x, &prop)?,
+                Value::Undefined(_) | Value::Null(_) => {
+                    return Err(Error::from(js_sys::TypeError::new(&format!(
+                        "TypeError: Cannot convert {} to object.",
+                        obj.borrow()
+                    ))))
+                }
+                _ => true,
+            };
+            Ok(Value::Bool(JsBool::from(deleted)).into())
+        }
+        _ => {
+            evaluator::expressions::eval_expr(expr, envs).await?;
+            Ok(Value::Bool(JsBool::from(true)).into())
+        }
+    }
+}
+
 #[inline]
 pub async fn eval_new_expr(newexpr: NewExpr, envs: &mut Environments) -> Result<RcValue, Error> {
     let function = evaluator::expressions::eval_expr(*newexpr.callee, envs).await?;
@@ -38,14 +38,15 @@ pub(crate) async fn eval_decl(decl: Decl, envs: &mut Environments) -> Result<RcV
                 envs,
                 None,
             )
-            .await
-            .map(|x| x.into())?;
-            envs.insert(&decl.ident.sym, function)?;
+            .await?;
+            functions::set_function_name(function.as_ref(), &decl.ident.sym)?;
+            envs.insert(&decl.ident.sym, function.into())?;
             Ok(Value::Undefined(JsValue::undefined()).into())
         }
         Decl::Class(classdecl) => {
             let ident = classdecl.ident;
             let class = class::eval_class(*classdecl.class, envs).await?;
+            functions::set_function_name(&class, &ident.sym)?;
             envs.insert(&ident.sym, Value::JsFunction(class).into())?;
             Ok(Value::Undefined(JsValue::undefined()).into())
         }
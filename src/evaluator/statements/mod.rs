@@ -3,12 +3,28 @@ use crate::evaluator::*;
 
 use futures::future::{FutureExt, LocalBoxFuture};
 use js_sys::Error;
+use swc_common::Spanned;
 
 pub(crate) mod decl;
 
 pub(crate) fn eval_stmt<'a>(
     stmt: Stmt,
     envs: &'a mut Environments,
+) -> LocalBoxFuture<'a, Result<RcValue, Error>> {
+    async move {
+        let span = stmt.span();
+        let result = eval_stmt_inner(stmt, envs).await;
+        if result.is_err() {
+            envs.error_span.get_or_insert(span);
+        }
+        result
+    }
+    .boxed_local()
+}
+
+fn eval_stmt_inner<'a>(
+    stmt: Stmt,
+    envs: &'a mut Environments,
 ) -> LocalBoxFuture<'a, Result<RcValue, Error>> {
     async move {
         match stmt {
@@ -36,12 +52,16 @@ pub(crate) fn eval_stmt<'a>(
                     envs.pop_env();
                     result
                 };
+                let interrupted = envs.realm.borrow().interrupt.is_interrupted();
                 future::ready(result)
                     .or_else(|x| async move {
                         match handler {
+                            // Interrupted cells cannot catch the interruption.
+                            Some(_) if interrupted => Err(x),
                             Some(y) => {
                                 future::ok(y)
                                     .and_then(|y| async move {
+                                        envs.error_span = None;
                                         envs.push_env();
                                         y.param.and_then(|z| match z {
                                             Pat::Ident(ident) => envs
@@ -64,6 +84,7 @@ pub(crate) fn eval_stmt<'a>(
                     .await
             }
             Stmt::While(while_stmt) => {
+                let interrupt = envs.realm.borrow().interrupt.clone();
                 while match &expressions::eval_expr(*while_stmt.test.clone(), envs)
                     .await?
                     .borrow() as &Value
@@ -71,6 +92,7 @@ pub(crate) fn eval_stmt<'a>(
                     Value::Bool(bool) => bool.value_of(),
                     _ => false,
                 } {
+                    interrupt.check().await?;
                     envs.push_env();
                     let body = *while_stmt.body.clone();
                     match body {
@@ -104,7 +126,9 @@ pub(crate) fn eval_stmt<'a>(
                 Ok(Value::Undefined(JsValue::undefined()).into())
             }
             Stmt::DoWhile(while_stmt) => {
+                let interrupt = envs.realm.borrow().interrupt.clone();
                 loop {
+                    interrupt.check().await?;
                     envs.push_env();
                     let body = *while_stmt.body.clone();
                     match body {
@@ -159,7 +183,9 @@ pub(crate) fn eval_stmt<'a>(
                     }
                     None => (),
                 };
+                let interrupt = envs.realm.borrow().interrupt.clone();
                 loop {
+                    interrupt.check().await?;
                     match for_stmt.test.clone() {
                         Some(x) => {
                             if match &expressions::eval_expr(*x, envs).await?.borrow() as &Value {
@@ -212,7 +238,9 @@ pub(crate) fn eval_stmt<'a>(
             Stmt::ForOf(for_of_stmt) => {
                 let object = expressions::eval_expr(*for_of_stmt.right, envs).await?;
                 let iterator = crate::builtin::iterator::get_iterator(object.borrow().as_ref())?;
+                let interrupt = envs.realm.borrow().interrupt.clone();
                 for x in iterator.into_iter() {
+                    interrupt.check().await?;
                     envs.push_env();
                     match for_of_stmt.left.clone() {
                         ForHead::Pat(pat) => {
@@ -278,7 +306,9 @@ pub(crate) fn eval_stmt<'a>(
                             y
                         ))),
                     }?);
+                let interrupt = envs.realm.borrow().interrupt.clone();
                 for x in right.iter().into_iter() {
+                    interrupt.check().await?;
                     envs.push_env();
                     match for_in_stmt.left.clone() {
                         ForHead::Pat(pat) => {
@@ -349,19 +379,25 @@ enum LoopBlock {
 
 #[inline]
 async fn eval_block<'env>(stmts: Vec<Stmt>, envs: &mut Environments) -> Result<LoopBlock, Error> {
-    stream::iter(stmts)
+    let declared = super::statement_names(&stmts);
+    envs.enter_lexical(declared.iter().flatten().cloned());
+    let result = stream::iter(stmts.into_iter().zip(declared))
         .fold(
             Ok((
                 LoopBlock::Normal(Value::Undefined(JsValue::undefined()).into()),
-                envs,
+                &mut *envs,
             )),
-            |acc, x| async move {
+            |acc, (x, declared)| async move {
                 let acc = acc?;
                 let result = match acc.0 {
                     LoopBlock::Normal(_) => match x {
                         Stmt::Break(_) => LoopBlock::Break,
                         Stmt::Continue(_) => LoopBlock::Continue,
-                        _ => LoopBlock::Normal(statements::eval_stmt(x, acc.1).await?),
+                        _ => {
+                            let result = statements::eval_stmt(x, acc.1).await;
+                            acc.1.initialize(&declared);
+                            LoopBlock::Normal(result?)
+                        }
                     },
                     y => y,
                 };
@@ -369,7 +405,9 @@ async fn eval_block<'env>(stmts: Vec<Stmt>, envs: &mut Environments) -> Result<L
             },
         )
         .await
-        .map(|x| x.0)
+        .map(|x| x.0);
+    envs.exit_lexical();
+    result
 }
 
 #[inline]
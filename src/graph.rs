@@ -0,0 +1,421 @@
+use js_sys::Error;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use swc_ecma_ast::*;
+use swc_ecma_visit::{Visit, VisitWith};
+
+/// The top-level names a cell declares and the names it reads from outside, e.g. from other
+/// cells or globals.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CellBindings {
+    pub defines: BTreeSet<String>,
+    pub reads: BTreeSet<String>,
+    /// Ids of the cells it imports from, statically or with `import()` of a string literal.
+    pub imports: BTreeSet<String>,
+}
+
+impl CellBindings {
+    pub fn from_module(module: &Module) -> CellBindings {
+        let mut defines = HashSet::new();
+        for item in &module.body {
+            match item {
+                ModuleItem::Stmt(stmt) => {
+                    lexical_names(std::slice::from_ref(stmt), &mut defines);
+                    stmt.visit_with(&mut VarNames(&mut defines));
+                }
+                ModuleItem::ModuleDecl(ModuleDecl::Import(import)) => {
+                    for specifier in &import.specifiers {
+                        let local = match specifier {
+                            ImportSpecifier::Named(x) => &x.local,
+                            ImportSpecifier::Default(x) => &x.local,
+                            ImportSpecifier::Namespace(x) => &x.local,
+                        };
+                        defines.insert(local.sym.to_string());
+                    }
+                }
+                ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export)) => {
+                    decl_names(&export.decl, &mut defines);
+                    export.decl.visit_with(&mut VarNames(&mut defines));
+                }
+                ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(export)) => {
+                    let ident = match &export.decl {
+                        DefaultDecl::Class(x) => x.ident.as_ref(),
+                        DefaultDecl::Fn(x) => x.ident.as_ref(),
+                        DefaultDecl::TsInterfaceDecl(_) => None,
+                    };
+                    defines.extend(ident.map(|x| x.sym.to_string()));
+                }
+                ModuleItem::ModuleDecl(_) => (),
+            }
+        }
+        let mut visitor = FreeVariables {
+            scopes: vec![defines],
+            free: BTreeSet::new(),
+        };
+        module.visit_with(&mut visitor);
+        let mut imports = CellImports(BTreeSet::new());
+        module.visit_with(&mut imports);
+        CellBindings {
+            defines: visitor.scopes.remove(0).into_iter().collect(),
+            reads: visitor.free,
+            imports: imports.0,
+        }
+    }
+}
+
+/// Cells by id with the names they define and read. A cell depends on the cells defining the
+/// names it reads and on the cells it imports from.
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    cells: BTreeMap<String, Cell>,
+    next: usize,
+}
+
+#[derive(Debug)]
+struct Cell {
+    source: String,
+    bindings: CellBindings,
+    /// Position of the cell in the order cells were first evaluated, which breaks ties
+    /// between cells that can run in any order.
+    position: usize,
+}
+
+impl DependencyGraph {
+    /// Records the source and bindings of a cell, replacing those of a previous evaluation.
+    pub fn update(&mut self, id: &str, source: String, bindings: CellBindings) {
+        let position = self.cells.get(id).map(|x| x.position).unwrap_or_else(|| {
+            self.next += 1;
+            self.next
+        });
+        self.cells.insert(
+            String::from(id),
+            Cell {
+                source,
+                bindings,
+                position,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        self.cells.remove(id);
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    pub fn source(&self, id: &str) -> Option<&str> {
+        self.cells.get(id).map(|x| x.source.as_str())
+    }
+
+    /// Returns the ids of the cells that directly depend on `id`.
+    fn readers<'a>(&'a self, id: &'a str) -> impl Iterator<Item = &'a String> + 'a {
+        let defines = self.cells.get(id).map(|x| &x.bindings.defines);
+        self.cells.iter().filter_map(move |(other, cell)| {
+            let reads = other != id
+                && (defines.is_some_and(|x| !x.is_disjoint(&cell.bindings.reads))
+                    || cell.bindings.imports.contains(id));
+            reads.then_some(other)
+        })
+    }
+
+    /// Returns the cells that depend on `id` directly or indirectly, in an order in which they
+    /// can be re-run: each cell comes after the cells it depends on.
+    pub fn dependents(&self, id: &str) -> Result<Vec<String>, Error> {
+        if !self.cells.contains_key(id) {
+            return Err(Error::from(js_sys::ReferenceError::new(&format!(
+                "ReferenceError: There is no cell {:?}.",
+                id
+            ))));
+        }
+        let mut affected = BTreeSet::from([id]);
+        let mut pending = vec![id];
+        while let Some(cell) = pending.pop() {
+            for reader in self.readers(cell) {
+                if affected.insert(reader.as_str()) {
+                    pending.push(reader);
+                }
+            }
+        }
+        // Kahn's algorithm on the affected cells.
+        let mut inputs: BTreeMap<&str, usize> = affected.iter().map(|x| (*x, 0)).collect();
+        for cell in &affected {
+            for reader in self.readers(cell) {
+                *inputs.get_mut(reader.as_str()).unwrap() += 1;
+            }
+        }
+        let mut order = Vec::new();
+        while let Some(cell) = inputs
+            .iter()
+            .filter(|x| *x.1 == 0)
+            .map(|x| *x.0)
+            .min_by_key(|x| self.cells[*x].position)
+        {
+            inputs.remove(cell);
+            for reader in self.readers(cell) {
+                if let Some(count) = inputs.get_mut(reader.as_str()) {
+                    *count -= 1;
+                }
+            }
+            order.push(cell);
+        }
+        if !inputs.is_empty() {
+            let cells = inputs
+                .keys()
+                .map(|x| format!("{:?}", x))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(Error::from(js_sys::ReferenceError::new(&format!(
+                "ReferenceError: Circular definition between the cells {}.",
+                cells
+            ))));
+        }
+        Ok(order
+            .into_iter()
+            .filter(|x| *x != id)
+            .map(String::from)
+            .collect())
+    }
+}
+
+/// Collects the names a pattern binds.
+fn pat_names(pat: &Pat, names: &mut HashSet<String>) {
+    match pat {
+        Pat::Ident(x) => {
+            names.insert(x.id.sym.to_string());
+        }
+        Pat::Array(x) => x.elems.iter().flatten().for_each(|x| pat_names(x, names)),
+        Pat::Rest(x) => pat_names(&x.arg, names),
+        Pat::Object(x) => {
+            for prop in &x.props {
+                match prop {
+                    ObjectPatProp::KeyValue(x) => pat_names(&x.value, names),
+                    ObjectPatProp::Assign(x) => {
+                        names.insert(x.key.sym.to_string());
+                    }
+                    ObjectPatProp::Rest(x) => pat_names(&x.arg, names),
+                }
+            }
+        }
+        Pat::Assign(x) => pat_names(&x.left, names),
+        Pat::Invalid(_) | Pat::Expr(_) => (),
+    }
+}
+
+fn decl_names(decl: &Decl, names: &mut HashSet<String>) {
+    match decl {
+        Decl::Class(x) => {
+            names.insert(x.ident.sym.to_string());
+        }
+        Decl::Fn(x) => {
+            names.insert(x.ident.sym.to_string());
+        }
+        Decl::Var(x) => lexical_var_names(x, names),
+        _ => (),
+    }
+}
+
+/// Collects the names declared by `let` and `const`.
+fn lexical_var_names(decl: &VarDecl, names: &mut HashSet<String>) {
+    if decl.kind != VarDeclKind::Var {
+        decl.decls.iter().for_each(|x| pat_names(&x.name, names));
+    }
+}
+
+/// Collects the names a declaration declares with `let` and `const`, which cannot be accessed
+/// before the declaration is evaluated.
+pub(crate) fn uninitialized_names(decl: &Decl, names: &mut HashSet<String>) {
+    if let Decl::Var(x) = decl {
+        lexical_var_names(x, names);
+    }
+}
+
+/// Collects the names declared by classes, functions, `let` and `const` directly in a block.
+pub(crate) fn lexical_names(stmts: &[Stmt], names: &mut HashSet<String>) {
+    for stmt in stmts {
+        if let Stmt::Decl(decl) = stmt {
+            decl_names(decl, names);
+        }
+    }
+}
+
+/// Collects the names declared with `var`, which are scoped to the enclosing function.
+struct VarNames<'a>(&'a mut HashSet<String>);
+
+impl Visit for VarNames<'_> {
+    fn visit_var_decl(&mut self, decl: &VarDecl) {
+        if decl.kind == VarDeclKind::Var {
+            decl.decls.iter().for_each(|x| pat_names(&x.name, self.0));
+        }
+        decl.visit_children_with(self);
+    }
+
+    fn visit_function(&mut self, _: &Function) {}
+
+    fn visit_arrow_expr(&mut self, _: &ArrowExpr) {}
+
+    fn visit_class(&mut self, _: &Class) {}
+}
+
+/// Collects the ids of the cells imported with `cell:` specifiers.
+struct CellImports(BTreeSet<String>);
+
+impl CellImports {
+    fn specifier(&mut self, specifier: &Str) {
+        if let Some(id) = specifier.value.strip_prefix("cell:") {
+            self.0.insert(String::from(id));
+        }
+    }
+}
+
+impl Visit for CellImports {
+    fn visit_import_decl(&mut self, import: &ImportDecl) {
+        self.specifier(&import.src);
+    }
+
+    fn visit_named_export(&mut self, export: &NamedExport) {
+        if let Some(src) = &export.src {
+            self.specifier(src);
+        }
+    }
+
+    fn visit_export_all(&mut self, export: &ExportAll) {
+        self.specifier(&export.src);
+    }
+
+    fn visit_call_expr(&mut self, call: &CallExpr) {
+        if let (Callee::Import(_), Some(arg)) = (&call.callee, call.args.first()) {
+            if let Expr::Lit(Lit::Str(specifier)) = &*arg.expr {
+                self.specifier(specifier);
+            }
+        }
+        call.visit_children_with(self);
+    }
+}
+
+/// Collects the identifiers read outside of the scopes that declare them.
+struct FreeVariables {
+    scopes: Vec<HashSet<String>>,
+    free: BTreeSet<String>,
+}
+
+impl FreeVariables {
+    fn reference(&mut self, name: &str) {
+        if !self.scopes.iter().any(|x| x.contains(name)) {
+            self.free.insert(String::from(name));
+        }
+    }
+
+    fn scoped(&mut self, names: HashSet<String>, visit: impl FnOnce(&mut FreeVariables)) {
+        self.scopes.push(names);
+        visit(self);
+        self.scopes.pop();
+    }
+
+    fn function_scope(params: &[&Pat], body: Option<&BlockStmt>) -> HashSet<String> {
+        let mut names = HashSet::from([String::from("arguments")]);
+        params.iter().for_each(|x| pat_names(x, &mut names));
+        if let Some(body) = body {
+            lexical_names(&body.stmts, &mut names);
+            body.visit_children_with(&mut VarNames(&mut names));
+        }
+        names
+    }
+}
+
+impl Visit for FreeVariables {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Ident(ident) => self.reference(&ident.sym),
+            _ => expr.visit_children_with(self),
+        }
+    }
+
+    fn visit_prop(&mut self, prop: &Prop) {
+        match prop {
+            Prop::Shorthand(ident) => self.reference(&ident.sym),
+            _ => prop.visit_children_with(self),
+        }
+    }
+
+    fn visit_assign_expr(&mut self, assign: &AssignExpr) {
+        // Assigning a variable does not read it.
+        let target = match &assign.left {
+            PatOrExpr::Pat(pat) => {
+                matches!(&**pat, Pat::Ident(_))
+                    || matches!(&**pat, Pat::Expr(expr) if expr.is_ident())
+            }
+            PatOrExpr::Expr(expr) => expr.is_ident(),
+        };
+        if assign.op != AssignOp::Assign || !target {
+            assign.left.visit_with(self);
+        }
+        assign.right.visit_with(self);
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        let params = function.params.iter().map(|x| &x.pat).collect::<Vec<_>>();
+        let names = FreeVariables::function_scope(&params, function.body.as_ref());
+        self.scoped(names, |x| function.visit_children_with(x));
+    }
+
+    fn visit_fn_expr(&mut self, function: &FnExpr) {
+        let names = function.ident.iter().map(|x| x.sym.to_string()).collect();
+        self.scoped(names, |x| function.function.visit_with(x));
+    }
+
+    fn visit_arrow_expr(&mut self, arrow: &ArrowExpr) {
+        let params = arrow.params.iter().collect::<Vec<_>>();
+        let names = FreeVariables::function_scope(&params, arrow.body.as_block_stmt());
+        self.scoped(names, |x| arrow.visit_children_with(x));
+    }
+
+    fn visit_class_expr(&mut self, class: &ClassExpr) {
+        let names = class.ident.iter().map(|x| x.sym.to_string()).collect();
+        self.scoped(names, |x| class.class.visit_with(x));
+    }
+
+    fn visit_block_stmt(&mut self, block: &BlockStmt) {
+        let mut names = HashSet::new();
+        lexical_names(&block.stmts, &mut names);
+        self.scoped(names, |x| block.visit_children_with(x));
+    }
+
+    fn visit_for_stmt(&mut self, stmt: &ForStmt) {
+        let mut names = HashSet::new();
+        if let Some(VarDeclOrExpr::VarDecl(decl)) = &stmt.init {
+            lexical_var_names(decl, &mut names);
+        }
+        self.scoped(names, |x| stmt.visit_children_with(x));
+    }
+
+    fn visit_for_in_stmt(&mut self, stmt: &ForInStmt) {
+        let names = for_head_names(&stmt.left);
+        self.scoped(names, |x| stmt.visit_children_with(x));
+    }
+
+    fn visit_for_of_stmt(&mut self, stmt: &ForOfStmt) {
+        let names = for_head_names(&stmt.left);
+        self.scoped(names, |x| stmt.visit_children_with(x));
+    }
+
+    fn visit_catch_clause(&mut self, clause: &CatchClause) {
+        let mut names = HashSet::new();
+        clause.param.iter().for_each(|x| pat_names(x, &mut names));
+        self.scoped(names, |x| clause.visit_children_with(x));
+    }
+
+    fn visit_member_prop(&mut self, prop: &MemberProp) {
+        if let MemberProp::Computed(computed) = prop {
+            computed.visit_with(self);
+        }
+    }
+}
+
+fn for_head_names(head: &ForHead) -> HashSet<String> {
+    let mut names = HashSet::new();
+    if let ForHead::VarDecl(decl) = head {
+        lexical_var_names(decl, &mut names);
+    }
+    names
+}
@@ -0,0 +1,282 @@
+use futures::future::{FutureExt, LocalBoxFuture};
+use js_sys::{Error, Reflect};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Debug;
+use std::rc::Rc;
+use swc_common::BytePos;
+use swc_ecma_ast::*;
+use swc_ecma_parser::{lexer::Lexer, Parser, StringInput, Syntax};
+use swc_ecma_visit::{Visit, VisitWith};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+use super::resolver::{is_relative, is_url};
+use super::{fetch_source, import_js};
+
+/// Storage for the source of modules, keyed by resolved URL.
+pub trait ModuleStore: Debug {
+    fn get<'a>(&'a self, url: &'a str) -> LocalBoxFuture<'a, Result<Option<String>, Error>>;
+
+    fn put<'a>(&'a self, url: &'a str, source: &'a str) -> LocalBoxFuture<'a, Result<(), Error>>;
+}
+
+/// Keeps modules for the lifetime of the interpreter.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    modules: RefCell<HashMap<String, String>>,
+}
+
+impl ModuleStore for MemoryStore {
+    fn get<'a>(&'a self, url: &'a str) -> LocalBoxFuture<'a, Result<Option<String>, Error>> {
+        let source = self.modules.borrow().get(url).cloned();
+        async move { Ok(source) }.boxed_local()
+    }
+
+    fn put<'a>(&'a self, url: &'a str, source: &'a str) -> LocalBoxFuture<'a, Result<(), Error>> {
+        self.modules
+            .borrow_mut()
+            .insert(String::from(url), String::from(source));
+        async move { Ok(()) }.boxed_local()
+    }
+}
+
+/// Storage provided by the host, e.g. on top of IndexedDB. The host object has the methods
+/// `get(url)`, which returns the source or `undefined`, and `put(url, source)`. Both may
+/// return promises.
+#[derive(Debug)]
+pub struct HostStore {
+    store: JsValue,
+    get: js_sys::Function,
+    put: js_sys::Function,
+}
+
+impl HostStore {
+    pub fn new(store: JsValue) -> Result<HostStore, Error> {
+        let method = |name: &str| {
+            Reflect::get(&store, &JsValue::from_str(name))?
+                .dyn_into::<js_sys::Function>()
+                .map_err(|_| {
+                    Error::from(js_sys::TypeError::new(&format!(
+                        "TypeError: The module store must have a method {:?}.",
+                        name
+                    )))
+                })
+        };
+        Ok(HostStore {
+            get: method("get")?,
+            put: method("put")?,
+            store,
+        })
+    }
+}
+
+impl ModuleStore for HostStore {
+    fn get<'a>(&'a self, url: &'a str) -> LocalBoxFuture<'a, Result<Option<String>, Error>> {
+        async move {
+            let source = self.get.call1(&self.store, &JsValue::from_str(url))?;
+            let source = JsFuture::from(js_sys::Promise::resolve(&source)).await?;
+            Ok(source.as_string())
+        }
+        .boxed_local()
+    }
+
+    fn put<'a>(&'a self, url: &'a str, source: &'a str) -> LocalBoxFuture<'a, Result<(), Error>> {
+        async move {
+            let result = self.put.call2(
+                &self.store,
+                &JsValue::from_str(url),
+                &JsValue::from_str(source),
+            )?;
+            JsFuture::from(js_sys::Promise::resolve(&result)).await?;
+            Ok(())
+        }
+        .boxed_local()
+    }
+}
+
+/// Loads modules through a `ModuleStore`, so that modules fetched once are available offline.
+///
+/// Static imports of cached modules are loaded through the cache as well, and rewritten to the
+/// URLs the cached modules are evaluated from.
+#[derive(Debug)]
+pub struct ModuleCache {
+    store: Rc<dyn ModuleStore>,
+    /// URLs the modules of this session are evaluated from, keyed by resolved URL.
+    loaded: RefCell<HashMap<String, String>>,
+    /// Resolved URLs of all modules used, including dependencies.
+    used: RefCell<BTreeSet<String>>,
+}
+
+impl ModuleCache {
+    pub fn new(store: Rc<dyn ModuleStore>) -> ModuleCache {
+        ModuleCache {
+            store,
+            loaded: RefCell::new(HashMap::new()),
+            used: RefCell::new(BTreeSet::new()),
+        }
+    }
+
+    pub async fn import(&self, url: &str) -> Result<JsValue, Error> {
+        let url = self.load(url, &mut Vec::new()).await?;
+        import_js(&url).await.map_err(Error::from)
+    }
+
+    /// Adds the modules of a bundle, an object mapping resolved URLs to sources.
+    pub async fn seed(&self, bundle: &JsValue) -> Result<(), Error> {
+        for entry in js_sys::Object::entries(&js_sys::Object::from(bundle.clone())).iter() {
+            let entry = js_sys::Array::from(&entry);
+            let url = entry.get(0).as_string().unwrap_or_default();
+            let source = entry.get(1).as_string().ok_or_else(|| {
+                Error::from(js_sys::TypeError::new(&format!(
+                    "TypeError: The source of the module {:?} must be a string.",
+                    url
+                )))
+            })?;
+            self.store.put(&url, &source).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns a bundle of all modules used so far, which can be passed to `seed`.
+    pub async fn export(&self) -> Result<js_sys::Object, Error> {
+        let bundle = js_sys::Object::new();
+        let used = self.used.borrow().clone();
+        for url in used {
+            if let Some(source) = self.store.get(&url).await? {
+                Reflect::set(
+                    &bundle,
+                    &JsValue::from_str(&url),
+                    &JsValue::from_str(&source),
+                )?;
+            }
+        }
+        Ok(bundle)
+    }
+
+    /// Returns the URL the module is evaluated from. `loading` contains the modules whose
+    /// imports are being loaded, imports that form a cycle are left to the network.
+    fn load<'a>(
+        &'a self,
+        url: &'a str,
+        loading: &'a mut Vec<String>,
+    ) -> LocalBoxFuture<'a, Result<String, Error>> {
+        async move {
+            let loaded = self.loaded.borrow().get(url).cloned();
+            if let Some(loaded) = loaded {
+                return Ok(loaded);
+            }
+            self.used.borrow_mut().insert(String::from(url));
+            let source = match self.store.get(url).await? {
+                Some(source) => source,
+                None => {
+                    let source = fetch_source(url).await?;
+                    self.store.put(url, &source).await?;
+                    source
+                }
+            };
+            loading.push(String::from(url));
+            let mut rewritten = String::with_capacity(source.len());
+            let mut last = 0;
+            for (lo, hi, specifier, is_static) in dependencies(url, &source)? {
+                if !(is_relative(&specifier) || is_url(&specifier)) {
+                    continue;
+                }
+                let dependency = web_sys::Url::new_with_base(&specifier, url)
+                    .map_err(|_| {
+                        Error::from(js_sys::TypeError::new(&format!(
+                            "TypeError: Cannot resolve {:?} from {:?}.",
+                            specifier, url
+                        )))
+                    })?
+                    .href();
+                let dependency = if is_static && !loading.contains(&dependency) {
+                    self.load(&dependency, loading).await?
+                } else {
+                    dependency
+                };
+                rewritten.push_str(&source[last..lo]);
+                rewritten.push_str(
+                    &js_sys::JSON::stringify(&JsValue::from_str(&dependency))?
+                        .as_string()
+                        .unwrap_or_default(),
+                );
+                last = hi;
+            }
+            rewritten.push_str(&source[last..]);
+            loading.pop();
+            let module_url = format!(
+                "data:text/javascript;charset=utf-8,{}",
+                js_sys::encode_uri_component(&rewritten)
+            );
+            self.loaded
+                .borrow_mut()
+                .insert(String::from(url), module_url.clone());
+            Ok(module_url)
+        }
+        .boxed_local()
+    }
+}
+
+/// Returns the byte ranges of the specifier literals of a module's imports, with the
+/// specifier and whether the import is static.
+fn dependencies(url: &str, source: &str) -> Result<Vec<(usize, usize, String, bool)>, Error> {
+    let lexer = Lexer::new(
+        Syntax::Es(Default::default()),
+        EsVersion::latest(),
+        StringInput::new(source, BytePos(1), BytePos(source.len() as u32 + 1)),
+        None,
+    );
+    let module = Parser::new_from(lexer).parse_module().map_err(|x| {
+        Error::from(js_sys::SyntaxError::new(&format!(
+            "SyntaxError: Cannot parse module {:?}: {}",
+            url,
+            x.kind().msg()
+        )))
+    })?;
+    let mut visitor = Dependencies(Vec::new());
+    module.visit_with(&mut visitor);
+    let mut dependencies = visitor.0;
+    dependencies.sort_by_key(|x| x.0);
+    Ok(dependencies)
+}
+
+struct Dependencies(Vec<(usize, usize, String, bool)>);
+
+impl Dependencies {
+    fn push(&mut self, src: &Str, is_static: bool) {
+        self.0.push((
+            src.span.lo.0 as usize - 1,
+            src.span.hi.0 as usize - 1,
+            src.value.to_string(),
+            is_static,
+        ));
+    }
+}
+
+impl Visit for Dependencies {
+    fn visit_import_decl(&mut self, import: &ImportDecl) {
+        self.push(&import.src, true);
+    }
+
+    fn visit_named_export(&mut self, export: &NamedExport) {
+        if let Some(src) = &export.src {
+            self.push(src, true);
+        }
+    }
+
+    fn visit_export_all(&mut self, export: &ExportAll) {
+        self.push(&export.src, true);
+    }
+
+    fn visit_call_expr(&mut self, call: &CallExpr) {
+        if let (Callee::Import(_), Some(ExprOrSpread { spread: None, expr })) =
+            (&call.callee, call.args.first())
+        {
+            if let Expr::Lit(Lit::Str(src)) = &**expr {
+                self.push(src, false);
+            }
+        }
+        call.visit_children_with(self);
+    }
+}
@@ -0,0 +1,208 @@
+use js_sys::{Error, Reflect};
+use wasm_bindgen::prelude::*;
+
+use super::resolver::{document_url, is_relative};
+
+/// Address of an import map entry, or the reason why the entry was rejected. Rejected entries
+/// are kept so that importing their specifiers fails instead of silently using the CDN.
+type Address = Result<String, String>;
+
+/// An import map as specified by the WICG, with top-level `imports` and `scopes`.
+///
+/// Specifier keys and addresses are normalized when the map is parsed, and the entries are
+/// sorted so that longer keys are matched first.
+#[derive(Clone, Debug, Default)]
+pub struct ImportMap {
+    imports: Vec<(String, Address)>,
+    scopes: Vec<(String, Vec<(String, Address)>)>,
+}
+
+impl ImportMap {
+    /// Parses an import map from its JSON text or from an object with the same structure.
+    /// Relative URLs are resolved against `base` or the location of the document.
+    pub fn parse(input: &JsValue, base: Option<&str>) -> Result<ImportMap, Error> {
+        let base = base.map(String::from).or_else(document_url);
+        let base = base.as_deref();
+        let map = match input.as_string() {
+            Some(json) => js_sys::JSON::parse(&json).map_err(|_| {
+                Error::from(js_sys::SyntaxError::new(
+                    "SyntaxError: The import map is not valid JSON.",
+                ))
+            })?,
+            None => input.clone(),
+        };
+        if !is_plain_object(&map) {
+            return Err(import_map_error("The import map must be a JSON object."));
+        }
+        let imports = Reflect::get(&map, &JsValue::from_str("imports"))?;
+        let imports = if imports.is_undefined() {
+            Vec::new()
+        } else if is_plain_object(&imports) {
+            parse_specifier_map(&imports, base)?
+        } else {
+            return Err(import_map_error("\"imports\" must be a JSON object."));
+        };
+        let scopes = Reflect::get(&map, &JsValue::from_str("scopes"))?;
+        let scopes = if scopes.is_undefined() {
+            Vec::new()
+        } else if is_plain_object(&scopes) {
+            let mut normalized = js_sys::Object::entries(&js_sys::Object::from(scopes))
+                .iter()
+                .map(|entry| {
+                    let entry = js_sys::Array::from(&entry);
+                    let prefix = entry.get(0).as_string().unwrap_or_default();
+                    let prefix = parse_url(&prefix, base).ok_or_else(|| {
+                        import_map_error(&format!("The scope {:?} is not a valid URL.", prefix))
+                    })?;
+                    if !is_plain_object(&entry.get(1)) {
+                        return Err(import_map_error(&format!(
+                            "The scope {:?} must be a JSON object.",
+                            prefix
+                        )));
+                    }
+                    Ok((prefix, parse_specifier_map(&entry.get(1), base)?))
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            normalized.sort_by(|a, b| b.0.cmp(&a.0));
+            normalized
+        } else {
+            return Err(import_map_error("\"scopes\" must be a JSON object."));
+        };
+        Ok(ImportMap { imports, scopes })
+    }
+
+    /// Resolves a specifier imported from `referrer`. Returns `None` if no entry matches,
+    /// in which case the specifier is left to the module resolver.
+    pub fn resolve(
+        &self,
+        specifier: &str,
+        referrer: Option<&str>,
+    ) -> Result<Option<String>, Error> {
+        let base = referrer.map(String::from).or_else(document_url);
+        let normalized = parse_url(specifier, base.as_deref()).unwrap_or(String::from(specifier));
+        if let Some(referrer) = base.as_deref() {
+            for (prefix, imports) in &self.scopes {
+                if prefix == referrer || (prefix.ends_with('/') && referrer.starts_with(prefix)) {
+                    if let Some(url) = resolve_imports_match(&normalized, imports)? {
+                        return Ok(Some(url));
+                    }
+                }
+            }
+        }
+        resolve_imports_match(&normalized, &self.imports)
+    }
+
+    /// Returns the normalized map, with `null` for rejected entries.
+    pub fn to_js(&self) -> Result<JsValue, Error> {
+        let map = js_sys::Object::new();
+        Reflect::set(
+            &map,
+            &JsValue::from_str("imports"),
+            &specifier_map_to_js(&self.imports)?,
+        )?;
+        let scopes = js_sys::Object::new();
+        for (prefix, imports) in &self.scopes {
+            Reflect::set(
+                &scopes,
+                &JsValue::from_str(prefix),
+                &specifier_map_to_js(imports)?,
+            )?;
+        }
+        Reflect::set(&map, &JsValue::from_str("scopes"), &scopes)?;
+        Ok(JsValue::from(map))
+    }
+}
+
+fn parse_specifier_map(map: &JsValue, base: Option<&str>) -> Result<Vec<(String, Address)>, Error> {
+    let mut normalized = js_sys::Object::entries(&js_sys::Object::from(map.clone()))
+        .iter()
+        .filter_map(|entry| {
+            let entry = js_sys::Array::from(&entry);
+            let key = entry.get(0).as_string().unwrap_or_default();
+            if key.is_empty() {
+                return None;
+            }
+            let key = parse_url(&key, base).unwrap_or(key);
+            let address = match entry.get(1).as_string() {
+                Some(address) => match parse_url(&address, base) {
+                    Some(url) if key.ends_with('/') && !url.ends_with('/') => Err(format!(
+                        "the address {:?} of the package prefix {:?} must end with \"/\"",
+                        url, key
+                    )),
+                    Some(url) => Ok(url),
+                    None => Err(format!("the address {:?} is not a valid URL", address)),
+                },
+                None => Err(String::from("the address must be a string")),
+            };
+            Some((key, address))
+        })
+        .collect::<Vec<_>>();
+    normalized.sort_by(|a, b| b.0.cmp(&a.0));
+    Ok(normalized)
+}
+
+fn resolve_imports_match(
+    specifier: &str,
+    imports: &[(String, Address)],
+) -> Result<Option<String>, Error> {
+    for (key, address) in imports {
+        if key == specifier {
+            return address
+                .clone()
+                .map(Some)
+                .map_err(|x| invalid_entry(key, &x));
+        }
+        if let (true, Some(rest)) = (key.ends_with('/'), specifier.strip_prefix(key.as_str())) {
+            let address = address.as_ref().map_err(|x| invalid_entry(key, x))?;
+            return match web_sys::Url::new_with_base(rest, address) {
+                Ok(url) if url.href().starts_with(address.as_str()) => Ok(Some(url.href())),
+                _ => Err(Error::from(js_sys::TypeError::new(&format!(
+                    "TypeError: The import map cannot resolve {:?} with the entry {:?}.",
+                    specifier, key
+                )))),
+            };
+        }
+    }
+    Ok(None)
+}
+
+fn specifier_map_to_js(imports: &[(String, Address)]) -> Result<JsValue, Error> {
+    let map = js_sys::Object::new();
+    for (key, address) in imports {
+        let value = match address {
+            Ok(url) => JsValue::from_str(url),
+            Err(_) => JsValue::null(),
+        };
+        Reflect::set(&map, &JsValue::from_str(key), &value)?;
+    }
+    Ok(JsValue::from(map))
+}
+
+/// Parses URL-like specifiers, i.e. absolute URLs and URLs starting with `/`, `./` or `../`.
+fn parse_url(input: &str, base: Option<&str>) -> Option<String> {
+    if is_relative(input) {
+        web_sys::Url::new_with_base(input, base?)
+            .ok()
+            .map(|x| x.href())
+    } else {
+        web_sys::Url::new(input).ok().map(|x| x.href())
+    }
+}
+
+fn is_plain_object(value: &JsValue) -> bool {
+    value.is_object() && !js_sys::Array::is_array(value)
+}
+
+fn import_map_error(message: &str) -> Error {
+    Error::from(js_sys::TypeError::new(&format!(
+        "TypeError: Invalid import map: {}",
+        message
+    )))
+}
+
+fn invalid_entry(key: &str, reason: &str) -> Error {
+    Error::from(js_sys::TypeError::new(&format!(
+        "TypeError: The import map entry {:?} is invalid: {}.",
+        key, reason
+    )))
+}
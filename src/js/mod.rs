@@ -1,5 +1,14 @@
 use js_sys::Error;
 use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+pub use cache::{HostStore, MemoryStore, ModuleCache, ModuleStore};
+pub use import_map::ImportMap;
+pub use resolver::*;
+
+mod cache;
+mod import_map;
+mod resolver;
 
 #[wasm_bindgen(
     inline_js = "export function importjs(str){return import(/* webpackIgnore: true */str)}"
@@ -9,13 +18,127 @@ extern "C" {
     pub async fn import_js(input: &str) -> Result<JsValue, JsValue>;
 }
 
-pub async fn import(input: &str) -> Result<JsValue, Error> {
-    if input.starts_with("https://cdn.skypack.dev") || input.starts_with("https://cdn.jsdelivr.net")
-    {
-        import_js(input).await.map_err(|x| Error::from(x))
+/// Resolves a specifier imported from `referrer`. The import map is consulted first,
+/// specifiers it does not map are resolved by `resolver`.
+pub fn resolve(
+    input: &str,
+    referrer: Option<&str>,
+    import_map: &ImportMap,
+    resolver: &dyn ModuleResolver,
+) -> Result<String, Error> {
+    match import_map.resolve(input, referrer)? {
+        Some(url) => Ok(url),
+        None => resolver.resolve(input, referrer),
+    }
+}
+
+/// Imports a module natively, through `cache` if one is given.
+pub async fn import(url: &str, cache: Option<&ModuleCache>) -> Result<JsValue, Error> {
+    match cache {
+        Some(cache) => cache.import(url).await,
+        None => import_js(url).await.map_err(Error::from),
+    }
+}
+
+/// Resolves a specifier of `require` like `resolve`.
+pub fn resolve_commonjs(
+    input: &str,
+    referrer: Option<&str>,
+    import_map: &ImportMap,
+    resolver: &dyn ModuleResolver,
+) -> Result<String, Error> {
+    match import_map.resolve(input, referrer)? {
+        Some(url) => Ok(url),
+        None => resolver.resolve_commonjs(input, referrer),
+    }
+}
+
+/// Fetches the source of a CommonJS module and returns it with its URL after redirects. As in
+/// Node, `.js` and `/index.js` are appended to URLs that are not found.
+pub async fn fetch_commonjs(url: &str) -> Result<(String, String), Error> {
+    let candidates = [
+        String::from(url),
+        format!("{}.js", url),
+        format!("{}/index.js", url.trim_end_matches('/')),
+    ];
+    for candidate in candidates.iter() {
+        let response = fetch(candidate).await?;
+        if response.ok() {
+            let source = response_text(candidate, &response).await?;
+            return Ok((response.url(), source));
+        } else if response.status() != 404 {
+            return Err(fetch_error(candidate, &response));
+        }
+    }
+    Err(Error::from(js_sys::TypeError::new(&format!(
+        "TypeError: Cannot find module {:?}.",
+        url
+    ))))
+}
+
+pub(crate) async fn fetch_source(url: &str) -> Result<String, Error> {
+    let response = fetch(url).await?;
+    if !response.ok() {
+        return Err(fetch_error(url, &response));
+    }
+    response_text(url, &response).await
+}
+
+async fn fetch(url: &str) -> Result<web_sys::Response, Error> {
+    let fetch = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("fetch"))?
+        .dyn_into::<js_sys::Function>()
+        .map_err(|_| Error::new("ERROR: fetch is not available."))?;
+    let response = fetch.call1(&JsValue::undefined(), &JsValue::from_str(url))?;
+    let response = JsFuture::from(js_sys::Promise::from(response)).await?;
+    Ok(web_sys::Response::from(response))
+}
+
+async fn response_text(url: &str, response: &web_sys::Response) -> Result<String, Error> {
+    JsFuture::from(response.text()?)
+        .await?
+        .as_string()
+        .ok_or_else(|| Error::new(&format!("ERROR: Module {:?} is not text.", url)))
+}
+
+/// Creates the error of a failed request of a module, with the HTTP status as `status`
+/// property, which `fetch_status` reads.
+fn fetch_error(url: &str, response: &web_sys::Response) -> Error {
+    let error = Error::from(js_sys::TypeError::new(&format!(
+        "TypeError: Failed to fetch module {:?}: {} {}.",
+        url,
+        response.status(),
+        response.status_text()
+    )));
+    let _ = js_sys::Reflect::set(
+        &error,
+        &JsValue::from_str("status"),
+        &JsValue::from(response.status()),
+    );
+    error
+}
+
+/// Returns the HTTP status of the failed request an error is caused by, e.g. to explain why
+/// importing a module failed. `None` for other errors, including failed native imports.
+pub(crate) fn fetch_status(error: &Error) -> Option<u16> {
+    js_sys::Reflect::get(error, &JsValue::from_str("status"))
+        .ok()
+        .and_then(|x| x.as_f64())
+        .map(|x| x as u16)
+}
+
+/// Asks the host's module loader for the source of a module. Returns `None` if the loader
+/// leaves the module to be imported natively.
+pub async fn load_source(loader: &js_sys::Function, url: &str) -> Result<Option<String>, Error> {
+    let source = loader.call1(&JsValue::undefined(), &JsValue::from_str(url))?;
+    let source = JsFuture::from(js_sys::Promise::resolve(&source)).await?;
+    if source.is_null() || source.is_undefined() {
+        Ok(None)
     } else {
-        let mut temp = String::from("https://cdn.skypack.dev/");
-        temp.push_str(input);
-        import_js(&temp).await.map_err(|x| Error::from(x))
+        source.as_string().map(Some).ok_or_else(|| {
+            Error::from(js_sys::TypeError::new(&format!(
+                "TypeError: The module loader must return a string for {:?}.",
+                url
+            )))
+        })
     }
 }
@@ -0,0 +1,183 @@
+use js_sys::Error;
+use std::fmt::Debug;
+use wasm_bindgen::prelude::*;
+
+/// Maps import specifiers to the URLs modules are loaded from.
+pub trait ModuleResolver: Debug {
+    /// Resolves a bare specifier such as `d3` or `lodash/fp`.
+    fn resolve_bare(&self, specifier: &str) -> Result<String, Error>;
+
+    /// Resolves a specifier. URLs are returned unchanged, relative specifiers are resolved
+    /// against `base` or the location of the document, and all other specifiers are bare.
+    fn resolve(&self, specifier: &str, base: Option<&str>) -> Result<String, Error> {
+        if is_url(specifier) {
+            Ok(String::from(specifier))
+        } else if is_relative(specifier) {
+            resolve_relative(specifier, base)
+        } else {
+            self.resolve_bare(specifier)
+        }
+    }
+
+    /// Resolves a bare specifier to the files of the package as published to npm, which
+    /// `require` evaluates as CommonJS.
+    fn resolve_bare_commonjs(&self, specifier: &str) -> Result<String, Error>;
+
+    /// Resolves a specifier of `require` like `resolve`, using `resolve_bare_commonjs` for
+    /// bare specifiers.
+    fn resolve_commonjs(&self, specifier: &str, base: Option<&str>) -> Result<String, Error> {
+        if is_url(specifier) {
+            Ok(String::from(specifier))
+        } else if is_relative(specifier) {
+            resolve_relative(specifier, base)
+        } else {
+            self.resolve_bare_commonjs(specifier)
+        }
+    }
+}
+
+/// Loads packages from [esm.sh](https://esm.sh). As esm.sh only serves ES modules, `require`
+/// loads packages from jsDelivr.
+#[derive(Debug, Default)]
+pub struct EsmSh;
+
+impl ModuleResolver for EsmSh {
+    fn resolve_bare(&self, specifier: &str) -> Result<String, Error> {
+        Ok(format!("https://esm.sh/{}", specifier))
+    }
+
+    fn resolve_bare_commonjs(&self, specifier: &str) -> Result<String, Error> {
+        JsDelivr.resolve_bare_commonjs(specifier)
+    }
+}
+
+/// Loads packages from jsDelivr, which bundles them as ES modules with `+esm`.
+#[derive(Debug, Default)]
+pub struct JsDelivr;
+
+impl ModuleResolver for JsDelivr {
+    fn resolve_bare(&self, specifier: &str) -> Result<String, Error> {
+        Ok(format!("https://cdn.jsdelivr.net/npm/{}/+esm", specifier))
+    }
+
+    fn resolve_bare_commonjs(&self, specifier: &str) -> Result<String, Error> {
+        Ok(format!("https://cdn.jsdelivr.net/npm/{}", specifier))
+    }
+}
+
+/// Loads packages from unpkg, which rewrites bare imports of the package with `?module`.
+#[derive(Debug, Default)]
+pub struct Unpkg;
+
+impl ModuleResolver for Unpkg {
+    fn resolve_bare(&self, specifier: &str) -> Result<String, Error> {
+        Ok(format!("https://unpkg.com/{}?module", specifier))
+    }
+
+    fn resolve_bare_commonjs(&self, specifier: &str) -> Result<String, Error> {
+        Ok(format!("https://unpkg.com/{}", specifier))
+    }
+}
+
+/// Loads packages from a custom base URL, e.g. an internal mirror of a CDN.
+#[derive(Debug)]
+pub struct BaseUrl {
+    base: String,
+}
+
+impl BaseUrl {
+    pub fn new(base: &str) -> BaseUrl {
+        let mut base = String::from(base);
+        if !base.ends_with('/') {
+            base.push('/');
+        }
+        BaseUrl { base }
+    }
+}
+
+impl ModuleResolver for BaseUrl {
+    fn resolve_bare(&self, specifier: &str) -> Result<String, Error> {
+        Ok(format!("{}{}", self.base, specifier))
+    }
+
+    fn resolve_bare_commonjs(&self, specifier: &str) -> Result<String, Error> {
+        self.resolve_bare(specifier)
+    }
+}
+
+/// Treats bare specifiers as URLs relative to the document, e.g. for packages served next to
+/// the notebook.
+#[derive(Debug, Default)]
+pub struct RelativeUrl;
+
+impl ModuleResolver for RelativeUrl {
+    fn resolve_bare(&self, specifier: &str) -> Result<String, Error> {
+        resolve_relative(specifier, None)
+    }
+
+    fn resolve_bare_commonjs(&self, specifier: &str) -> Result<String, Error> {
+        self.resolve_bare(specifier)
+    }
+}
+
+/// Creates one of the built-in resolvers by name.
+pub fn builtin_resolver(
+    strategy: &str,
+    base_url: Option<&str>,
+) -> Result<Box<dyn ModuleResolver>, Error> {
+    match (strategy, base_url) {
+        ("esm.sh", _) => Ok(Box::new(EsmSh)),
+        ("jsdelivr", _) => Ok(Box::new(JsDelivr)),
+        ("unpkg", _) => Ok(Box::new(Unpkg)),
+        ("base-url", Some(base)) => Ok(Box::new(BaseUrl::new(base))),
+        ("relative", _) => Ok(Box::new(RelativeUrl)),
+        ("base-url", None) => Err(Error::from(js_sys::TypeError::new(
+            "TypeError: The resolver \"base-url\" requires a base URL.",
+        ))),
+        _ => Err(Error::from(js_sys::TypeError::new(&format!(
+            "TypeError: Unknown module resolver {:?}. Expected one of \"esm.sh\", \"jsdelivr\", \"unpkg\", \"base-url\" or \"relative\".",
+            strategy
+        )))),
+    }
+}
+
+pub(super) fn is_url(specifier: &str) -> bool {
+    ["https:", "http:", "data:", "blob:"]
+        .iter()
+        .any(|x| specifier.starts_with(x))
+}
+
+pub(super) fn is_relative(specifier: &str) -> bool {
+    specifier.starts_with("./") || specifier.starts_with("../") || specifier.starts_with('/')
+}
+
+fn resolve_relative(specifier: &str, base: Option<&str>) -> Result<String, Error> {
+    let base = match base {
+        Some(base) => String::from(base),
+        None => document_url().ok_or_else(|| {
+            Error::from(js_sys::TypeError::new(&format!(
+                "TypeError: Cannot resolve {:?} without a base URL.",
+                specifier
+            )))
+        })?,
+    };
+    web_sys::Url::new_with_base(specifier, &base)
+        .map(|x| x.href())
+        .map_err(|_| {
+            Error::from(js_sys::TypeError::new(&format!(
+                "TypeError: Cannot resolve {:?} against {:?}.",
+                specifier, base
+            )))
+        })
+}
+
+pub(super) fn document_url() -> Option<String> {
+    let document = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("document")).ok()?;
+    if document.is_object() {
+        js_sys::Reflect::get(&document, &JsValue::from_str("baseURI"))
+            .ok()?
+            .as_string()
+    } else {
+        None
+    }
+}
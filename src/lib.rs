@@ -4,30 +4,19 @@
 /// - Thorsten Ball - Writing an interpreter in Go
 /// - How JavaScript Objects are Implemented - https://www.infoq.com/presentations/javascript-objects-spidermonkey/
 ///
-use futures::stream::{Peekable, StreamExt};
-use futures::SinkExt;
-use js_sys::Error;
-use lazy_static::lazy_static;
-use std::cell::RefCell;
-use std::pin::Pin;
-use value::Value;
 use wasm_bindgen::prelude::*;
-
-use futures::channel::mpsc;
-use futures::lock::Mutex;
+use wasm_bindgen_futures::JsFuture;
 
 pub use crate::environment::Environments;
-use swc_common::sync::Lrc;
-use swc_common::{
-    errors::{ColorConfig, Handler},
-    FileName, SourceMap,
-};
-use swc_ecma_parser::{lexer::Lexer, EsConfig, Parser, StringInput, Syntax};
+pub use crate::session::Session;
 
 mod builtin;
+mod display;
 mod environment;
 mod evaluator;
+mod graph;
 mod js;
+mod session;
 mod transform;
 mod utils;
 mod value;
@@ -47,122 +36,159 @@ extern "C" {
     fn log(s: &str);
 }
 thread_local! {
-    pub static ENVS: RefCell<Environments> = RefCell::new(Environments::new());
+    /// The session of the free functions below, for hosts with a single notebook.
+    static DEFAULT_SESSION: Session = Session::new();
 }
 
-lazy_static! {
-    pub static ref CHANNEL: (
-        Mutex<mpsc::UnboundedSender<String>>,
-        Mutex<Peekable<mpsc::UnboundedReceiver<String>>>
-    ) = {
-        let channel = mpsc::unbounded::<String>();
-        (Mutex::new(channel.0), Mutex::new(channel.1.peekable()))
-    };
+fn default_session() -> Session {
+    DEFAULT_SESSION.with(Session::clone)
 }
 
 #[wasm_bindgen]
 pub fn reset_envs() {
-    ENVS.with(|f| {
-        f.replace(Environments::new());
-    });
+    default_session().reset_envs()
+}
+
+#[wasm_bindgen]
+pub fn set_module_resolver(strategy: &str, base_url: Option<String>) -> Result<(), JsValue> {
+    default_session().set_module_resolver(strategy, base_url)
+}
+
+#[wasm_bindgen]
+pub fn set_import_map(import_map: JsValue) -> Result<(), JsValue> {
+    default_session().set_import_map(import_map)
+}
+
+#[wasm_bindgen]
+pub fn get_import_map() -> Result<JsValue, JsValue> {
+    default_session().get_import_map()
+}
+
+#[wasm_bindgen]
+pub fn set_module_loader(loader: Option<js_sys::Function>) {
+    default_session().set_module_loader(loader)
+}
+
+#[wasm_bindgen]
+pub fn set_capability_policy(policy: JsValue) -> Result<(), JsValue> {
+    default_session().set_capability_policy(policy)
+}
+
+#[wasm_bindgen]
+pub fn set_fetch_policy(policy: JsValue) -> Result<(), JsValue> {
+    default_session().set_fetch_policy(policy)
+}
+
+#[wasm_bindgen]
+pub fn set_output_trust(trusted: bool) {
+    default_session().set_output_trust(trusted)
+}
+
+#[wasm_bindgen]
+pub fn set_console_handler(handler: Option<js_sys::Function>) {
+    default_session().set_console_handler(handler)
+}
+
+#[wasm_bindgen]
+pub fn set_display_handler(handler: Option<js_sys::Function>) {
+    default_session().set_display_handler(handler)
+}
+
+#[wasm_bindgen]
+pub fn set_table_limits(limits: JsValue) -> Result<(), JsValue> {
+    default_session().set_table_limits(limits)
+}
+
+#[wasm_bindgen]
+pub fn set_deterministic_mode(mode: JsValue) -> Result<(), JsValue> {
+    default_session().set_deterministic_mode(mode)
+}
+
+#[wasm_bindgen]
+pub fn enable_module_cache(store: JsValue) -> Result<(), JsValue> {
+    default_session().enable_module_cache(store)
+}
+
+#[wasm_bindgen]
+pub async fn seed_modules(bundle: JsValue) -> Result<(), JsValue> {
+    JsFuture::from(default_session().seed_modules(bundle))
+        .await
+        .map(|_| ())
+}
+
+#[wasm_bindgen]
+pub async fn export_modules() -> Result<JsValue, JsValue> {
+    JsFuture::from(default_session().export_modules()).await
 }
 
 #[wasm_bindgen]
 pub async fn eval_cell(input: String) -> Result<JsValue, JsValue> {
-    async {
-        let mut tx = async { CHANNEL.0.lock().await.clone() }.await;
-        tx.send(input.clone()).await.unwrap();
-    }
-    .await;
-    let lock = CHANNEL.1.lock().await;
-    let mut pin = Pin::new(lock);
-    pin.as_mut().next_if_eq(&input).await;
-    let mut envs = Environments::empty();
-    ENVS.with(|f| {
-        envs = f.replace(Environments::empty());
-    });
-    let cm: Lrc<SourceMap> = Default::default();
-    let handler = Handler::with_tty_emitter(ColorConfig::Auto, true, false, Some(cm.clone()));
-
-    let fm = cm.new_source_file(FileName::Custom("Dashbook_cell".into()), input.into());
-    let es_config: EsConfig = Default::default();
-    let lexer = Lexer::new(
-        Syntax::Es(es_config),
-        Default::default(),
-        StringInput::from(&*fm),
-        None,
-    );
-
-    let mut parser = Parser::new_from(lexer);
-
-    for e in parser.take_errors() {
-        e.into_diagnostic(&handler).emit();
-    }
-
-    let result = match parser.parse_module() {
-        Ok(module) => evaluator::eval_module(module.body, &mut envs)
-            .await
-            .and_then(|x| x.borrow().output())
-            .or_else(|err| Ok(JsValue::from(err.message()))),
-        Err(err) => Ok(JsValue::from(
-            js_sys::SyntaxError::new(&err.kind().msg()).message(),
-        )),
-    };
-    ENVS.with(|f| {
-        f.replace(envs);
-    });
-    result
+    JsFuture::from(default_session().eval_cell(input)).await
+}
+
+#[wasm_bindgen]
+pub async fn eval_cell_with_id(id: String, source: String) -> Result<JsValue, JsValue> {
+    JsFuture::from(default_session().eval_cell_with_id(id, source)).await
+}
+
+#[wasm_bindgen]
+pub async fn eval_named_cell(name: String, input: String) -> Result<JsValue, JsValue> {
+    JsFuture::from(default_session().eval_named_cell(name, input)).await
+}
+
+#[wasm_bindgen]
+pub async fn execute_cell(id: Option<String>, source: String) -> Result<JsValue, JsValue> {
+    JsFuture::from(default_session().execute_cell(id, source)).await
+}
+
+#[wasm_bindgen]
+pub fn inspect(handle: u32, path: JsValue) -> Result<JsValue, JsValue> {
+    default_session().inspect(handle, path)
+}
+
+#[wasm_bindgen]
+pub fn interrupt() {
+    default_session().interrupt()
+}
+
+#[wasm_bindgen]
+pub fn cancel_cell(id: &str) {
+    default_session().cancel_cell(id)
+}
+
+#[wasm_bindgen]
+pub async fn delete_cell(id: String) -> Result<(), JsValue> {
+    JsFuture::from(default_session().delete_cell(id))
+        .await
+        .map(|_| ())
+}
+
+#[wasm_bindgen]
+pub fn get_dependents(cell_id: &str) -> Result<JsValue, JsValue> {
+    default_session().get_dependents(cell_id)
+}
+
+#[wasm_bindgen]
+pub async fn run_dependents(cell_id: String) -> Result<JsValue, JsValue> {
+    JsFuture::from(default_session().run_dependents(cell_id)).await
+}
+
+#[wasm_bindgen]
+pub fn get_cell_imports(cell_id: &str) -> Result<JsValue, JsValue> {
+    default_session().get_cell_imports(cell_id)
+}
+
+#[wasm_bindgen]
+pub fn get_cell_requests(cell_id: &str) -> Result<JsValue, JsValue> {
+    default_session().get_cell_requests(cell_id)
 }
 
 #[wasm_bindgen]
 pub fn list_properties(object: &str) -> js_sys::Object {
-    let mut properties = None;
-    ENVS.with(|f| {
-        properties = if object == "" {
-            f.borrow().stack[0]
-                .iter()
-                .map(|x| {
-                    Ok([
-                        JsValue::from_str(&x.0),
-                        crate::evaluator::expressions::unary::eval_typeof_operator(&x.1.borrow())?
-                            .into(),
-                    ]
-                    .into_iter()
-                    .collect::<js_sys::Array>())
-                })
-                .collect::<Result<js_sys::Array, Error>>()
-                .ok()
-        } else {
-            f.borrow().stack[0]
-                .get(object)
-                .and_then(|value| match &*value.borrow() {
-                    Value::Object(obj) => Some(
-                        js_sys::Object::entries(obj)
-                            .iter()
-                            .map(|tuple| {
-                                let tuple = js_sys::Array::from(&tuple);
-                                let value = tuple.pop();
-                                tuple.push(&value.js_typeof());
-                                tuple
-                            })
-                            .collect::<js_sys::Array>(),
-                    ),
-                    _ => None,
-                })
-        }
-    });
-    properties
-        .and_then(|x| js_sys::Object::from_entries(&x).ok())
-        .unwrap_or(js_sys::Object::new())
+    default_session().list_properties(object)
 }
 
 #[wasm_bindgen]
 pub fn get_type(object: &str) -> JsValue {
-    let mut type_ = None;
-    ENVS.with(|f| {
-        type_ = f.borrow().stack[0]
-            .get(object)
-            .map(|value| value.borrow().as_ref().js_typeof())
-    });
-    type_.unwrap_or(JsValue::from_str("undefined"))
+    default_session().get_type(object)
 }
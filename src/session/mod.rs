@@ -0,0 +1,727 @@
+use futures::channel::{mpsc, oneshot};
+use futures::future::{self, Either};
+use futures::lock::Mutex;
+use futures::stream::{Peekable, StreamExt};
+use futures::SinkExt;
+use js_sys::Error;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::future_to_promise;
+
+use crate::builtin::deterministic;
+use crate::display::inspect;
+use crate::environment::{self, Environments};
+use crate::graph::{CellBindings, DependencyGraph};
+use crate::value::Value;
+use crate::{builtin, display, evaluator, js};
+pub use result::{CellError, CellResult};
+
+mod result;
+use swc_common::sync::Lrc;
+use swc_common::{
+    errors::{ColorConfig, Handler},
+    FileName, SourceMap, Span, Spanned,
+};
+use swc_ecma_parser::{lexer::Lexer, EsConfig, Parser, StringInput, Syntax};
+
+/// A notebook with its own environments, configuration and queue of cells. Sessions share
+/// nothing, so several notebooks can run on the same page.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Session {
+    envs: Rc<RefCell<Environments>>,
+    /// Realm of the session, which the running cell shares while its environments are taken
+    /// out of `envs`, so that the host can configure and inspect it meanwhile.
+    realm: Rc<RefCell<environment::RcRealm>>,
+    queue: Rc<Queue>,
+    graph: Rc<RefCell<DependencyGraph>>,
+    interrupt: environment::Interrupt,
+}
+
+/// Cells run one at a time, in the order they were submitted.
+struct Queue {
+    sender: Mutex<mpsc::UnboundedSender<String>>,
+    receiver: Mutex<Peekable<mpsc::UnboundedReceiver<String>>>,
+    /// Cells submitted and not finished by the number they were submitted with, with their id
+    /// and whether they were cancelled.
+    pending: RefCell<BTreeMap<u32, (Option<String>, bool)>>,
+    /// Senders rejecting the results of the cells waiting to run when they are cancelled.
+    rejections: RefCell<BTreeMap<u32, oneshot::Sender<()>>>,
+    submitted: Cell<u32>,
+    /// Number of the cell running.
+    running: Cell<Option<u32>>,
+}
+
+impl Queue {
+    /// Adds a cell to the pending cells and returns its number with the receiver of its
+    /// rejection, which fires if it is cancelled before it runs.
+    fn submit(&self, cell: Option<String>) -> (u32, oneshot::Receiver<()>) {
+        let number = self.submitted.get() + 1;
+        self.submitted.set(number);
+        self.pending.borrow_mut().insert(number, (cell, false));
+        let (sender, receiver) = oneshot::channel();
+        self.rejections.borrow_mut().insert(number, sender);
+        (number, receiver)
+    }
+
+    /// Marks a cell as running, unless it was cancelled while it waited. Returns whether it
+    /// runs.
+    fn start(&self, number: u32) -> bool {
+        self.rejections.borrow_mut().remove(&number);
+        let cancelled = !matches!(self.pending.borrow().get(&number), Some((_, false)));
+        if cancelled {
+            self.pending.borrow_mut().remove(&number);
+        } else {
+            self.running.set(Some(number));
+        }
+        !cancelled
+    }
+
+    fn finish(&self, number: u32) {
+        self.pending.borrow_mut().remove(&number);
+        self.running.set(None);
+    }
+
+    /// Cancels the pending cells `cancel` selects by their id. The results of the cells
+    /// waiting to run are rejected at once, the cells leave the queue in order. Returns
+    /// whether the running cell was cancelled.
+    fn cancel(&self, cancel: impl Fn(Option<&str>) -> bool) -> bool {
+        let mut running = false;
+        for (number, (cell, cancelled)) in self.pending.borrow_mut().iter_mut() {
+            if cancel(cell.as_deref()) {
+                *cancelled = true;
+                running |= self.running.get() == Some(*number);
+                if let Some(rejection) = self.rejections.borrow_mut().remove(number) {
+                    let _ = rejection.send(());
+                }
+            }
+        }
+        running
+    }
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session::new()
+    }
+}
+
+#[wasm_bindgen]
+impl Session {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Session {
+        let channel = mpsc::unbounded::<String>();
+        let envs = Environments::new();
+        let interrupt = envs.realm.borrow().interrupt.clone();
+        Session {
+            realm: Rc::new(RefCell::new(envs.realm.clone())),
+            envs: Rc::new(RefCell::new(envs)),
+            queue: Rc::new(Queue {
+                sender: Mutex::new(channel.0),
+                receiver: Mutex::new(channel.1.peekable()),
+                pending: RefCell::new(BTreeMap::new()),
+                rejections: RefCell::new(BTreeMap::new()),
+                submitted: Cell::new(0),
+                running: Cell::new(None),
+            }),
+            graph: Rc::new(RefCell::new(DependencyGraph::default())),
+            interrupt,
+        }
+    }
+
+    /// Discards the variables, modules and cell dependencies of the session, keeping its
+    /// configuration.
+    pub fn reset_envs(&self) {
+        self.graph.borrow_mut().clear();
+        let realm = environment::RcRealm::default();
+        realm.borrow_mut().config = self.realm().borrow().config.clone();
+        realm.borrow_mut().interrupt = self.interrupt.clone();
+        self.realm.replace(realm.clone());
+        self.envs.replace(Environments::with_realm(realm));
+    }
+
+    /// Selects how bare import specifiers are resolved: `"esm.sh"` (the default), `"jsdelivr"`,
+    /// `"unpkg"`, `"base-url"` with the URL of a package mirror, or `"relative"` to the
+    /// document.
+    pub fn set_module_resolver(
+        &self,
+        strategy: &str,
+        base_url: Option<String>,
+    ) -> Result<(), JsValue> {
+        let resolver = js::builtin_resolver(strategy, base_url.as_deref())?;
+        self.realm().borrow_mut().config.resolver = resolver.into();
+        Ok(())
+    }
+
+    /// Sets the import map, given as JSON text or as an object with `imports` and `scopes`,
+    /// which pins the URLs of specifiers before they are passed to the module resolver. `null`
+    /// removes the import map.
+    pub fn set_import_map(&self, import_map: JsValue) -> Result<(), JsValue> {
+        let import_map = if import_map.is_null() || import_map.is_undefined() {
+            js::ImportMap::default()
+        } else {
+            js::ImportMap::parse(&import_map, None)?
+        };
+        self.realm().borrow_mut().config.import_map = import_map.into();
+        Ok(())
+    }
+
+    /// Returns the active import map with normalized URLs. Rejected entries are `null`.
+    pub fn get_import_map(&self) -> Result<JsValue, JsValue> {
+        let import_map = self.realm().borrow().config.import_map.clone();
+        Ok(import_map.to_js()?)
+    }
+
+    /// Sets a module loader, a function returning the source of a module URL or a promise of
+    /// it. Modules with source are interpreted like cells, other modules are imported natively.
+    /// Relative imports of cells resolve against `file:///`, e.g. `./utils.js` is loaded as
+    /// `file:///utils.js`, so the loader can serve the files of a project. Setting the loader
+    /// discards the modules it loaded before.
+    pub fn set_module_loader(&self, loader: Option<js_sys::Function>) {
+        let realm = self.realm();
+        let mut realm = realm.borrow_mut();
+        realm.config.loader = loader;
+        realm.local_modules.clear();
+    }
+
+    /// Sets which host APIs cells can use. `policy` is an object with the optional properties
+    /// `allow` and `deny`, arrays of globals such as `"fetch"` or members of the document such
+    /// as `"document.querySelector"`, `wrappers`, an object mapping these names to the values
+    /// cells get instead, and `documentQueries` to allow the read-only query methods of
+    /// `document`. `null` restores the default policy. Functions defined before keep the
+    /// previous policy, and a running cell keeps it until it finishes.
+    pub fn set_capability_policy(&self, policy: JsValue) -> Result<(), JsValue> {
+        let policy = if policy.is_null() || policy.is_undefined() {
+            environment::CapabilityPolicy::default()
+        } else {
+            environment::CapabilityPolicy::from_js(&policy)?
+        };
+        self.realm().borrow_mut().config.policy = policy.into();
+        self.update_globals()?;
+        Ok(())
+    }
+
+    /// Restricts the `fetch` of cells. `policy` is an object with the optional properties
+    /// `origins`, an array of the origins cells can fetch from (all origins if omitted),
+    /// `credentials`, whether requests send cookies (omitted by default), and `maxBytes`, the
+    /// maximum size of a response body. `null` restores the default policy.
+    pub fn set_fetch_policy(&self, policy: JsValue) -> Result<(), JsValue> {
+        let policy = if policy.is_null() || policy.is_undefined() {
+            builtin::fetch::FetchPolicy::default()
+        } else {
+            builtin::fetch::FetchPolicy::from_js(&policy)?
+        };
+        self.realm().borrow_mut().config.fetch = policy.into();
+        Ok(())
+    }
+
+    /// Trusts the DOM outputs of cells, which are then neither sanitized when they are
+    /// returned nor when they are built with `html`, `svg` and `element`. Only for notebooks
+    /// whose cells and data the host trusts.
+    pub fn set_output_trust(&self, trusted: bool) {
+        self.realm().borrow_mut().config.trust_output = trusted;
+    }
+
+    /// Streams the messages cells write to the console to `handler`, a function called with
+    /// an object with the `cell` the message is attributed to, the `level`, e.g. `"log"` or
+    /// `"table"`, the formatted `text` and the `depth` of groups, also for messages written by
+    /// callbacks after their cell finished. `null` removes the handler.
+    pub fn set_console_handler(&self, handler: Option<js_sys::Function>) {
+        self.realm().borrow_mut().config.console_handler = handler;
+    }
+
+    /// Passes the outputs cells show with `display(value, { id })` to `handler`, a function
+    /// called with an object with the `type`, `"display"` for a new output or `"update"` for
+    /// `display.update(id, value)`, the `cell` that showed the output, its `id`, the MIME bundle
+    /// of the value as `data` and the `error` message if it could not be rendered. Outputs are
+    /// passed in the order they were shown, including outputs callbacks show after their cell
+    /// finished. `null` removes the handler.
+    pub fn set_display_handler(&self, handler: Option<js_sys::Function>) {
+        self.realm().borrow_mut().config.display_handler = handler;
+    }
+
+    /// Limits the tables arrays of records and objects of arrays are rendered as. `limits` is
+    /// an object with the optional properties `pageSize`, the rows of a page of the HTML table
+    /// and of the text table, `maxRows`, `maxColumns` and `maxWidth`, the characters of a cell.
+    /// `null` restores the default limits.
+    pub fn set_table_limits(&self, limits: JsValue) -> Result<(), JsValue> {
+        let limits = if limits.is_null() || limits.is_undefined() {
+            display::TableLimits::default()
+        } else {
+            display::TableLimits::from_js(&limits)?
+        };
+        self.realm().borrow_mut().config.table_limits = limits;
+        Ok(())
+    }
+
+    /// Enables the deterministic mode, in which cells get the same random numbers and times on
+    /// every run. `mode` is an object with the `seed` of `Math.random` and
+    /// `crypto.getRandomValues`, a number or string, and the `startTime` of the virtual clock
+    /// of `Date` and `performance.now`. Timers run in the order they are due without waiting,
+    /// advancing the virtual clock. `crypto` and `performance` are replaced even if the
+    /// capability policy hides them. The sequences restart when the environments are reset, and
+    /// a running cell keeps its globals until it finishes. `null` disables the mode.
+    pub fn set_deterministic_mode(&self, mode: JsValue) -> Result<(), JsValue> {
+        let mode = if mode.is_null() || mode.is_undefined() {
+            None
+        } else {
+            Some(builtin::deterministic::DeterministicMode::from_js(&mode)?)
+        };
+        {
+            let realm = self.realm();
+            let mut realm = realm.borrow_mut();
+            realm.config.deterministic = mode;
+            realm.deterministic = None;
+        }
+        self.update_globals()?;
+        Ok(())
+    }
+
+    /// Loads imported modules through a cache, so that they are available offline. `store` is
+    /// an object with the methods `get(url)` and `put(url, source)`, e.g. backed by IndexedDB.
+    /// Without a store, modules are cached in memory.
+    pub fn enable_module_cache(&self, store: JsValue) -> Result<(), JsValue> {
+        let store: Rc<dyn js::ModuleStore> = if store.is_null() || store.is_undefined() {
+            Rc::new(js::MemoryStore::default())
+        } else {
+            Rc::new(js::HostStore::new(store)?)
+        };
+        self.realm().borrow_mut().config.module_cache = Some(Rc::new(js::ModuleCache::new(store)));
+        Ok(())
+    }
+
+    /// Adds modules to the cache from a bundle, an object mapping resolved URLs to sources as
+    /// returned by `export_modules`. Enables the in-memory cache if no cache is enabled.
+    pub fn seed_modules(&self, bundle: JsValue) -> js_sys::Promise {
+        let cache = {
+            let realm = self.realm();
+            let mut realm = realm.borrow_mut();
+            realm
+                .config
+                .module_cache
+                .get_or_insert_with(|| {
+                    Rc::new(js::ModuleCache::new(Rc::new(js::MemoryStore::default())))
+                })
+                .clone()
+        };
+        future_to_promise(async move {
+            cache.seed(&bundle).await?;
+            Ok(JsValue::undefined())
+        })
+    }
+
+    /// Returns a bundle of the modules used by the notebook, including their dependencies.
+    pub fn export_modules(&self) -> js_sys::Promise {
+        let cache = self.realm().borrow().config.module_cache.clone();
+        future_to_promise(async move {
+            match cache {
+                Some(cache) => Ok(JsValue::from(cache.export().await?)),
+                None => Ok(JsValue::from(js_sys::Object::new())),
+            }
+        })
+    }
+
+    /// Evaluates a cell after the cells submitted before and returns a promise of its output,
+    /// or of its error message.
+    pub fn eval_cell(&self, input: String) -> js_sys::Promise {
+        let result = self.run_cell(input, None);
+        future_to_promise(async move { Ok(result.await.output) })
+    }
+
+    /// Evaluates the cell `id`. The top-level bindings of its previous evaluation are dropped
+    /// first, so that renamed variables disappear. Its exports are published under `id`, from
+    /// where other cells of the session can import them with `import { x } from "cell:<id>"`.
+    pub fn eval_cell_with_id(&self, id: String, source: String) -> js_sys::Promise {
+        let result = self.run_cell(source, Some(id));
+        future_to_promise(async move { Ok(result.await.output) })
+    }
+
+    /// Evaluates a cell and publishes its exports under `name`, like `eval_cell_with_id`.
+    pub fn eval_named_cell(&self, name: String, input: String) -> js_sys::Promise {
+        self.eval_cell_with_id(name, input)
+    }
+
+    /// Evaluates a cell like `eval_cell`, or like `eval_cell_with_id` if `id` is given, and
+    /// returns a promise of its result: an object with the `status` (`"ok"` or `"error"`), the
+    /// `output` of `eval_cell`, the MIME bundle of the value as `data`, the `type` of the value,
+    /// the `error` with its `name`, `message`, `line` and `column`, the `duration` in
+    /// milliseconds, the messages the cell wrote to the `console`, a bounded `preview` of the
+    /// value, a `handle` to inspect it and the ids of the `stale` cells depending on it, which
+    /// still use the values of its previous evaluation, in the order they can be re-run.
+    pub fn execute_cell(&self, id: Option<String>, source: String) -> js_sys::Promise {
+        let result = self.run_cell(source, id);
+        future_to_promise(async move { Ok(result.await.to_js()?) })
+    }
+
+    /// Expands the value of a cell result: follows the keys of `path`, e.g. `["points", "0"]`,
+    /// from the value of `handle` and returns the `type` and `preview` of the value found with
+    /// its first 100 `entries`, each with a `key`, `preview`, `type` and whether it is
+    /// `expandable`, and the number of entries left out as `more`. Entries of maps and sets
+    /// are keyed by their index. Getters are called when the path goes through them.
+    pub fn inspect(&self, handle: u32, path: JsValue) -> Result<JsValue, JsValue> {
+        Ok(inspect::inspect(&self.realm(), handle, &path)?)
+    }
+
+    /// Interrupts the running cell and drops the cells waiting to run, whose results are
+    /// rejected at once with an `Interrupted` error. The running cell stops at its next loop iteration, function call or
+    /// `await`, keeping the variables it defined before. Callbacks it passed to host APIs, e.g.
+    /// timers, are not stopped.
+    pub fn interrupt(&self) {
+        if self.queue.cancel(|_| true) {
+            self.interrupt.interrupt();
+        }
+    }
+
+    /// Cancels the runs of the cell `id`: interrupts it if it is running and drops it if it
+    /// waits to run, like `interrupt`.
+    pub fn cancel_cell(&self, id: &str) {
+        if self.queue.cancel(|cell| cell == Some(id)) {
+            self.interrupt.interrupt();
+        }
+    }
+
+    /// Deletes the cell `id` after the cells submitted before: drops its top-level bindings,
+    /// its exports and its dependencies.
+    pub fn delete_cell(&self, id: String) -> js_sys::Promise {
+        let session = self.clone();
+        future_to_promise(async move {
+            let _lock = session.queue.receiver.lock().await;
+            let mut envs = session.envs.borrow_mut();
+            envs.drop_cell(&id);
+            {
+                let mut realm = envs.realm.borrow_mut();
+                realm.modules.remove(&id);
+                realm.imports.remove(&id);
+                realm.requests.remove(&id);
+                realm.handles.release(&id);
+                realm
+                    .displays
+                    .retain(|_, cell| cell.as_deref() != Some(&id));
+            }
+            session.graph.borrow_mut().remove(&id);
+            Ok(JsValue::undefined())
+        })
+    }
+
+    /// Returns the ids of the cells that read names the cell `cell_id` defines or import from
+    /// it, directly or through other cells, in an order in which they can be re-run. Only cells with an id are
+    /// tracked. Throws if the cells depend on each other in a cycle.
+    pub fn get_dependents(&self, cell_id: &str) -> Result<JsValue, JsValue> {
+        let dependents = self.graph.borrow().dependents(cell_id)?;
+        Ok(JsValue::from(
+            dependents
+                .iter()
+                .map(|x| JsValue::from_str(x))
+                .collect::<js_sys::Array>(),
+        ))
+    }
+
+    /// Re-runs the cells depending on the cell `cell_id`, e.g. after it was edited, and returns
+    /// a promise of their outputs, an array of objects with the `cell` id and its `output`.
+    /// Stops after a cell that was interrupted.
+    pub fn run_dependents(&self, cell_id: String) -> js_sys::Promise {
+        let session = self.clone();
+        future_to_promise(async move {
+            let dependents = session.graph.borrow().dependents(&cell_id)?;
+            let outputs = js_sys::Array::new();
+            for cell in dependents {
+                let source = session.graph.borrow().source(&cell).map(String::from);
+                if let Some(source) = source {
+                    let result = session.run_cell(source, Some(cell.clone())).await;
+                    let entry = js_sys::Object::new();
+                    js_sys::Reflect::set(&entry, &JsValue::from_str("cell"), &JsValue::from(cell))?;
+                    js_sys::Reflect::set(&entry, &JsValue::from_str("output"), &result.output)?;
+                    outputs.push(&entry);
+                    if result.is_interrupted() {
+                        break;
+                    }
+                }
+            }
+            Ok(JsValue::from(outputs))
+        })
+    }
+
+    /// Returns the imports of the last evaluation of a cell, `""` for cells evaluated with
+    /// `eval_cell`. Each import is an object with the `specifier`, the resolved `url`, the
+    /// pinned `version`, `status` (`"loaded"` or `"failed"`), the `error` message, the
+    /// `httpStatus` of failed requests (`null` for native imports), the `duration` in
+    /// milliseconds and the names of the `exports`.
+    pub fn get_cell_imports(&self, cell_id: &str) -> Result<JsValue, JsValue> {
+        let realm = self.realm();
+        let realm = realm.borrow();
+        let imports = realm
+            .imports
+            .get(cell_id)
+            .map(|x| x.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .map(|x| x.to_js())
+            .collect::<Result<js_sys::Array, Error>>()?;
+        Ok(JsValue::from(imports))
+    }
+
+    /// Returns the requests the last evaluation of a cell made with `fetch`, `""` for cells
+    /// evaluated with `eval_cell`. Each request is an object with the `url`, `method`, the HTTP
+    /// `status`, the size of the body in `bytes`, the `duration` in milliseconds and the
+    /// `error` message of failed requests.
+    pub fn get_cell_requests(&self, cell_id: &str) -> Result<JsValue, JsValue> {
+        let realm = self.realm();
+        let realm = realm.borrow();
+        let requests = realm
+            .requests
+            .get(cell_id)
+            .map(|x| x.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .map(|x| x.to_js())
+            .collect::<Result<js_sys::Array, Error>>()?;
+        Ok(JsValue::from(requests))
+    }
+
+    pub fn list_properties(&self, object: &str) -> js_sys::Object {
+        let envs = self.envs.borrow();
+        let properties = if object.is_empty() {
+            envs.stack[0]
+                .iter()
+                .map(|x| {
+                    Ok([
+                        JsValue::from_str(x.0),
+                        crate::evaluator::expressions::unary::eval_typeof_operator(&x.1.borrow())?
+                            .into(),
+                    ]
+                    .into_iter()
+                    .collect::<js_sys::Array>())
+                })
+                .collect::<Result<js_sys::Array, Error>>()
+                .ok()
+        } else {
+            envs.stack[0]
+                .get(object)
+                .and_then(|value| match &*value.borrow() {
+                    Value::Object(obj) => Some(
+                        js_sys::Object::entries(obj)
+                            .iter()
+                            .map(|tuple| {
+                                let tuple = js_sys::Array::from(&tuple);
+                                let value = tuple.pop();
+                                tuple.push(&value.js_typeof());
+                                tuple
+                            })
+                            .collect::<js_sys::Array>(),
+                    ),
+                    _ => None,
+                })
+        };
+        properties
+            .and_then(|x| js_sys::Object::from_entries(&x).ok())
+            .unwrap_or(js_sys::Object::new())
+    }
+
+    pub fn get_type(&self, object: &str) -> JsValue {
+        let type_ = self.envs.borrow().stack[0]
+            .get(object)
+            .map(|value| value.borrow().as_ref().js_typeof());
+        type_.unwrap_or(JsValue::from_str("undefined"))
+    }
+}
+
+impl Session {
+    /// Returns the realm of the session, also while a cell runs.
+    fn realm(&self) -> environment::RcRealm {
+        self.realm.borrow().clone()
+    }
+
+    /// Binds the globals again after a policy changed. The environments of a running cell
+    /// are taken out of the session, so they are bound again after the cell.
+    fn update_globals(&self) -> Result<(), Error> {
+        if self.queue.running.get().is_some() {
+            self.realm().borrow_mut().stale_globals = true;
+            Ok(())
+        } else {
+            self.envs.borrow_mut().insert_globals()
+        }
+    }
+
+    /// Submits a cell, which can then be cancelled, and returns the future of its result.
+    fn run_cell(
+        &self,
+        input: String,
+        name: Option<String>,
+    ) -> impl std::future::Future<Output = CellResult> {
+        let (number, rejected) = self.queue.submit(name.clone());
+        let (sender, result) = oneshot::channel();
+        let session = self.clone();
+        // The run goes on when the cell is rejected, so that it leaves the queue in order.
+        wasm_bindgen_futures::spawn_local(async move {
+            let _ = sender.send(session.run_submitted(number, input, name).await);
+        });
+        async move {
+            match future::select(result, rejected).await {
+                Either::Left((result, _)) => result.unwrap_or_else(|_| CellResult::interrupted()),
+                Either::Right((Ok(()), _)) => CellResult::interrupted(),
+                // The cell started, so it can no longer be rejected.
+                Either::Right((Err(_), result)) => {
+                    result.await.unwrap_or_else(|_| CellResult::interrupted())
+                }
+            }
+        }
+    }
+
+    async fn run_submitted(&self, number: u32, input: String, name: Option<String>) -> CellResult {
+        async {
+            let mut tx = async { self.queue.sender.lock().await.clone() }.await;
+            tx.send(input.clone()).await.unwrap();
+        }
+        .await;
+        let lock = self.queue.receiver.lock().await;
+        let mut pin = Pin::new(lock);
+        pin.as_mut().next_if_eq(&input).await;
+        if !self.queue.start(number) {
+            return CellResult::interrupted();
+        }
+        self.interrupt.reset();
+        let mut envs = self.envs.replace(Environments::empty());
+        let cm: Lrc<SourceMap> = Default::default();
+        let handler = Handler::with_tty_emitter(ColorConfig::Auto, true, false, Some(cm.clone()));
+
+        let fm = cm.new_source_file(FileName::Custom("Dashbook_cell".into()), input.clone());
+        let es_config: EsConfig = Default::default();
+        let lexer = Lexer::new(
+            Syntax::Es(es_config),
+            Default::default(),
+            StringInput::from(&*fm),
+            None,
+        );
+
+        let mut parser = Parser::new_from(lexer);
+
+        for e in parser.take_errors() {
+            e.into_diagnostic(&handler).emit();
+        }
+
+        envs.exports.clear();
+        envs.cell = name.clone();
+        envs.error_span = None;
+        let run = {
+            let mut realm = envs.realm.borrow_mut();
+            let cell = name.as_deref().unwrap_or_default();
+            realm.imports.remove(cell);
+            realm.requests.remove(cell);
+            realm.handles.release(cell);
+            // Outputs of cells without id are dropped when the next cell without id runs.
+            realm
+                .displays
+                .retain(|_, owner| owner.as_deref().unwrap_or_default() != cell);
+            realm.cell = name.clone();
+            realm.console_output = Some(Vec::new());
+            realm.console_run += 1;
+            realm.sources += 1;
+            envs.source = realm.sources;
+            realm.console_run
+        };
+        envs.bind_console(name.as_deref(), Some(run));
+        envs.bind_display(name.as_deref());
+        let location = |span: Span| {
+            (fm.start_pos <= span.lo && span.lo <= fm.end_pos).then(|| {
+                let loc = cm.lookup_char_pos(span.lo);
+                (loc.line, loc.col.0 + 1)
+            })
+        };
+        let start = deterministic::now(&envs.realm);
+        let mut stale = Vec::new();
+        let result = match parser.parse_module() {
+            Ok(module) => {
+                if let Some(name) = &name {
+                    let bindings = CellBindings::from_module(&module);
+                    envs.drop_cell(name);
+                    envs.own_bindings(name, bindings.defines.iter().cloned());
+                    let mut graph = self.graph.borrow_mut();
+                    graph.update(name, input, bindings);
+                    stale = graph.dependents(name).unwrap_or_default();
+                }
+                async {
+                    let value = evaluator::eval_module(module.body, &mut envs).await?;
+                    if let Some(name) = name {
+                        let namespace = evaluator::modules::module_namespace(&mut envs)?;
+                        envs.realm.borrow_mut().modules.insert(name, namespace);
+                    }
+                    let value = value.borrow().clone();
+                    let output = builtin::html::sanitize(&envs.realm, value.output()?)?;
+                    let data = display::mime_bundle(&value, &output, &envs.realm).await?;
+                    let inspection = (
+                        inspect::preview(&value, &envs.realm)?,
+                        inspect::handle(&value, envs.cell.clone(), &envs.realm),
+                    );
+                    Ok((output, data, display::type_tag(&value)?, inspection))
+                }
+                .await
+                .map_err(|err: Error| {
+                    let interrupted = self.interrupt.is_interrupted();
+                    let err = if interrupted {
+                        environment::interrupted_error()
+                    } else {
+                        err
+                    };
+                    let error = CellError::new(&err, envs.error_span.and_then(location));
+                    (JsValue::from(err.message()), error, interrupted)
+                })
+            }
+            Err(err) => {
+                let message = err.kind().msg();
+                let error = Error::from(js_sys::SyntaxError::new(&message));
+                Err((
+                    JsValue::from(message.as_ref()),
+                    CellError::new(&error, location(err.span())),
+                    false,
+                ))
+            }
+        };
+        let duration = deterministic::now(&envs.realm) - start;
+        let console = {
+            let mut realm = envs.realm.borrow_mut();
+            realm.cell = None;
+            realm.console_output.take().unwrap_or_default()
+        };
+        // Blocks left by a failed statement are dropped, so that the next cell declares its
+        // variables at the top level.
+        envs.stack.truncate(1);
+        if std::mem::take(&mut envs.realm.borrow_mut().stale_globals) {
+            // The stack has its global environment, so binding cannot fail.
+            let _ = envs.insert_globals();
+        }
+        // The environments of a realm reset while the cell ran are dropped.
+        if Rc::ptr_eq(&envs.realm, &self.realm()) {
+            self.envs.replace(envs);
+        }
+        self.queue.finish(number);
+        match result {
+            Ok((output, data, type_tag, (preview, handle))) => CellResult {
+                data: Some(data),
+                output,
+                type_tag: Some(type_tag),
+                preview: Some(preview),
+                handle,
+                error: None,
+                interrupted: false,
+                duration,
+                console,
+                stale,
+            },
+            Err((output, error, interrupted)) => CellResult {
+                output,
+                data: None,
+                type_tag: None,
+                preview: None,
+                handle: None,
+                error: Some(error),
+                interrupted,
+                duration,
+                console,
+                stale,
+            },
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use crate::builtin::console::ConsoleEntry;
+use js_sys::{Error, Reflect};
+use wasm_bindgen::prelude::*;
+
+/// The outcome of evaluating a cell.
+#[derive(Clone, Debug)]
+pub struct CellResult {
+    /// What `eval_cell` returns: the output of the value, a string or a DOM node, or the
+    /// message of the error.
+    pub output: JsValue,
+    /// MIME bundle of the value, `None` if the cell failed.
+    pub data: Option<js_sys::Object>,
+    /// Type of the value, e.g. `"number"` or `"Map"`, `None` if the cell failed.
+    pub type_tag: Option<String>,
+    /// Bounded preview of the value, e.g. `Point {x: 1, y: 2}`, `None` if the cell failed.
+    pub preview: Option<String>,
+    /// Handle the host can inspect the value through, `None` for values without entries.
+    pub handle: Option<u32>,
+    pub error: Option<CellError>,
+    /// Whether the cell was interrupted while it ran or dropped before it ran.
+    pub interrupted: bool,
+    /// Time the evaluation took in milliseconds.
+    pub duration: f64,
+    /// Messages the cell wrote to the console while it was evaluated.
+    pub console: Vec<ConsoleEntry>,
+    /// Cells depending on the evaluated cell, which still use the values of its previous
+    /// evaluation, in the order they can be re-run. Empty for cells without id and for cells
+    /// depending on each other in a cycle.
+    pub stale: Vec<String>,
+}
+
+/// An error a cell failed with.
+#[derive(Clone, Debug)]
+pub struct CellError {
+    /// Name of the error, e.g. `TypeError`.
+    pub name: String,
+    pub message: String,
+    /// Line and column of the statement that failed, starting at 1.
+    pub location: Option<(usize, usize)>,
+}
+
+impl CellError {
+    pub fn new(error: &Error, location: Option<(usize, usize)>) -> CellError {
+        let name = String::from(error.name());
+        let message = String::from(error.message());
+        // Messages of the interpreter start with the name of the error.
+        let message = match message.strip_prefix(&format!("{}: ", name)) {
+            Some(message) => String::from(message),
+            None => message,
+        };
+        CellError {
+            name,
+            message,
+            location,
+        }
+    }
+
+    pub fn to_js(&self) -> Result<JsValue, Error> {
+        let error = js_sys::Object::new();
+        let (line, column) = match self.location {
+            Some((line, column)) => (JsValue::from(line), JsValue::from(column)),
+            None => (JsValue::null(), JsValue::null()),
+        };
+        let entries = [
+            ("name", JsValue::from_str(&self.name)),
+            ("message", JsValue::from_str(&self.message)),
+            ("line", line),
+            ("column", column),
+        ];
+        for (key, value) in entries {
+            Reflect::set(&error, &JsValue::from_str(key), &value)?;
+        }
+        Ok(JsValue::from(error))
+    }
+}
+
+impl CellResult {
+    /// Returns the result of a cell that was dropped before it ran.
+    pub fn interrupted() -> CellResult {
+        let error = crate::environment::interrupted_error();
+        CellResult {
+            output: JsValue::from(error.message()),
+            data: None,
+            type_tag: None,
+            preview: None,
+            handle: None,
+            error: Some(CellError::new(&error, None)),
+            interrupted: true,
+            duration: 0.0,
+            console: Vec::new(),
+            stale: Vec::new(),
+        }
+    }
+
+    /// Returns whether the cell was interrupted or dropped.
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted
+    }
+
+    pub fn to_js(&self) -> Result<JsValue, Error> {
+        let result = js_sys::Object::new();
+        let status = match &self.error {
+            None => "ok",
+            Some(_) if self.is_interrupted() => "interrupted",
+            Some(_) => "error",
+        };
+        let entries = [
+            ("status", JsValue::from_str(status)),
+            ("output", self.output.clone()),
+            (
+                "data",
+                self.data
+                    .as_ref()
+                    .map(JsValue::from)
+                    .unwrap_or(JsValue::null()),
+            ),
+            (
+                "type",
+                self.type_tag
+                    .as_deref()
+                    .map(JsValue::from_str)
+                    .unwrap_or(JsValue::null()),
+            ),
+            (
+                "preview",
+                self.preview
+                    .as_deref()
+                    .map(JsValue::from_str)
+                    .unwrap_or(JsValue::null()),
+            ),
+            (
+                "handle",
+                self.handle.map(JsValue::from).unwrap_or(JsValue::null()),
+            ),
+            (
+                "error",
+                self.error
+                    .as_ref()
+                    .map(|x| x.to_js())
+                    .transpose()?
+                    .unwrap_or(JsValue::null()),
+            ),
+            ("duration", JsValue::from_f64(self.duration)),
+            (
+                "console",
+                JsValue::from(
+                    self.console
+                        .iter()
+                        .map(|x| x.to_js())
+                        .collect::<Result<js_sys::Array, Error>>()?,
+                ),
+            ),
+            (
+                "stale",
+                JsValue::from(
+                    self.stale
+                        .iter()
+                        .map(|x| JsValue::from_str(x))
+                        .collect::<js_sys::Array>(),
+                ),
+            ),
+        ];
+        for (key, value) in entries {
+            Reflect::set(&result, &JsValue::from_str(key), &value)?;
+        }
+        Ok(JsValue::from(result))
+    }
+}
@@ -0,0 +1,54 @@
+use crate::environment::Environments;
+use crate::test::eval::test_eval;
+
+use wasm_bindgen::JsCast;
+use wasm_bindgen_test::*;
+
+async fn outer_html(input: &str, envs: &mut Environments) -> String {
+  let output = test_eval(input, envs).await.unwrap();
+  let output = output.borrow().as_ref().clone();
+  output.unchecked_into::<web_sys::HtmlElement>().outer_html()
+}
+
+#[wasm_bindgen_test]
+async fn html_is_sanitized() {
+  let mut envs = Environments::new();
+  assert_eq!(
+    outer_html(
+      "let name = '<b>x</b>'; html`<div onclick=\"alert(1)\">${name}<script>alert(2)</script><a href=\"javascript:alert(3)\">a</a></div>`",
+      &mut envs
+    )
+    .await,
+    "<div>&lt;b&gt;x&lt;/b&gt;<a>a</a></div>"
+  );
+  assert_eq!(
+    outer_html("element('a', { href: 'https://example.com', onclick: 'x' }, 'link')", &mut envs).await,
+    "<a href=\"https://example.com\">link</a>"
+  );
+  assert_eq!(
+    outer_html("html`<div><style>body { display: none; }</style><p style=\"color: red\">a</p></div>`", &mut envs).await,
+    "<div><p style=\"color: red\">a</p></div>"
+  );
+  assert!(test_eval("element('script')", &mut envs).await.is_err());
+  assert!(test_eval("element('style')", &mut envs).await.is_err());
+}
+
+#[wasm_bindgen_test]
+async fn outputs_are_detached() {
+  let mut envs = Environments::new();
+  let node = test_eval("element('div', {}, 'a')", &mut envs).await.unwrap();
+  let node = node.borrow().as_ref().clone().unchecked_into::<web_sys::HtmlElement>();
+  let output = crate::builtin::html::sanitize(&envs.realm, node.clone().into()).unwrap();
+  node.set_attribute("onclick", "alert(1)").unwrap();
+  assert_eq!(output.unchecked_into::<web_sys::HtmlElement>().outer_html(), "<div>a</div>");
+}
+
+#[wasm_bindgen_test]
+async fn trusted_html() {
+  let mut envs = Environments::new();
+  envs.realm.borrow_mut().config.trust_output = true;
+  assert_eq!(
+    outer_html("html`<a href=\"javascript:void 0\">a</a>`", &mut envs).await,
+    "<a href=\"javascript:void 0\">a</a>"
+  );
+}
@@ -8,6 +8,10 @@ use crate::environment::Environments;
 use web_sys::Response;
 
 mod eval;
+mod html;
+mod modules;
+mod policy;
+mod session;
 mod setup;
 
 wasm_bindgen_test_configure!(run_in_browser);
@@ -23844,28 +23848,28 @@ success_identifiers_part_unicode_11_0_0_js,
 // "expressions/generators/dflt-params-trailing-comma.js",
 // expressions_generators_prototype_uniqueness_js,
 // "expressions/generators/prototype-uniqueness.js",
-// expressions_delete_11_4_1_4_a_4_js,
-// "expressions/delete/11.4.1-4.a-4.js",
+success_expressions_delete_11_4_1_4_a_4_js,
+"expressions/delete/11.4.1-4.a-4.js",
 // expressions_delete_11_4_1_5_a_28_s_js,
 // "expressions/delete/11.4.1-5-a-28-s.js",
 // expressions_delete_S11_4_1_A3_3_T2_js,
 // "expressions/delete/S11.4.1_A3.3_T2.js",
 // expressions_delete_S11_4_1_A3_2_T2_js,
 // "expressions/delete/S11.4.1_A3.2_T2.js",
-// expressions_delete_S8_12_7_A2_T2_js,
-// "expressions/delete/S8.12.7_A2_T2.js",
+success_expressions_delete_S8_12_7_A2_T2_js,
+"expressions/delete/S8.12.7_A2_T2.js",
 // expressions_delete_11_4_1_4_a_6_js,
 // "expressions/delete/11.4.1-4.a-6.js",
-// expressions_delete_S11_4_1_A2_2_T1_js,
-// "expressions/delete/S11.4.1_A2.2_T1.js",
+success_expressions_delete_S11_4_1_A2_2_T1_js,
+"expressions/delete/S11.4.1_A2.2_T1.js",
 // expressions_delete_11_4_1_4_a_10_js,
 // "expressions/delete/11.4.1-4.a-10.js",
-// expressions_delete_S11_4_1_A2_1_js,
-// "expressions/delete/S11.4.1_A2.1.js",
+success_expressions_delete_S11_4_1_A2_1_js,
+"expressions/delete/S11.4.1_A2.1.js",
 // expressions_delete_11_4_1_4_a_7_js,
 // "expressions/delete/11.4.1-4.a-7.js",
-// expressions_delete_11_4_1_3_3_js,
-// "expressions/delete/11.4.1-3-3.js",
+success_expressions_delete_11_4_1_3_3_js,
+"expressions/delete/11.4.1-3-3.js",
 // expressions_delete_11_4_1_5_3_js,
 // "expressions/delete/11.4.1-5-3.js",
 // expressions_delete_11_4_1_4_a_2_js,
@@ -23874,8 +23878,8 @@ success_identifiers_part_unicode_11_0_0_js,
 // "expressions/delete/11.4.1-4.a-13.js",
 // expressions_delete_S11_4_1_A4_js,
 // "expressions/delete/S11.4.1_A4.js",
-// expressions_delete_11_4_1_4_a_1_js,
-// "expressions/delete/11.4.1-4.a-1.js",
+success_expressions_delete_11_4_1_4_a_1_js,
+"expressions/delete/11.4.1-4.a-1.js",
 // expressions_delete_S11_4_1_A2_2_T3_js,
 // "expressions/delete/S11.4.1_A2.2_T3.js",
 // expressions_delete_11_4_1_4_a_2_s_js,
@@ -23892,12 +23896,12 @@ success_identifiers_part_unicode_11_0_0_js,
 // "expressions/delete/11.4.1-4.a-11.js",
 // expressions_delete_S11_4_1_A5_js,
 // "expressions/delete/S11.4.1_A5.js",
-// expressions_delete_11_4_1_4_a_15_js,
-// "expressions/delete/11.4.1-4.a-15.js",
+success_expressions_delete_11_4_1_4_a_15_js,
+"expressions/delete/11.4.1-4.a-15.js",
 // expressions_delete_11_4_1_4_a_3_s_js,
 // "expressions/delete/11.4.1-4-a-3-s.js",
-// expressions_delete_S8_12_7_A2_T1_js,
-// "expressions/delete/S8.12.7_A2_T1.js",
+success_expressions_delete_S8_12_7_A2_T1_js,
+"expressions/delete/S8.12.7_A2_T1.js",
 // expressions_delete_super_property_js,
 // "expressions/delete/super-property.js",
 // expressions_delete_11_4_1_4_a_1_s_js,
@@ -23906,38 +23910,38 @@ success_identifiers_part_unicode_11_0_0_js,
 // "expressions/delete/S11.4.1_A3.3_T6.js",
 // expressions_delete_S11_4_1_A3_3_T3_js,
 // "expressions/delete/S11.4.1_A3.3_T3.js",
-// expressions_delete_11_4_1_4_a_14_js,
-// "expressions/delete/11.4.1-4.a-14.js",
-// expressions_delete_11_4_1_4_a_12_js,
-// "expressions/delete/11.4.1-4.a-12.js",
+success_expressions_delete_11_4_1_4_a_14_js,
+"expressions/delete/11.4.1-4.a-14.js",
+success_expressions_delete_11_4_1_4_a_12_js,
+"expressions/delete/11.4.1-4.a-12.js",
 // expressions_delete_11_4_4_4_a_3_s_js,
 // "expressions/delete/11.4.4-4.a-3-s.js",
 // expressions_delete_S11_4_1_A2_2_T2_js,
 // "expressions/delete/S11.4.1_A2.2_T2.js",
 // expressions_delete_11_4_1_4_a_8_s_js,
 // "expressions/delete/11.4.1-4.a-8-s.js",
-// expressions_delete_11_4_1_4_a_9_js,
-// "expressions/delete/11.4.1-4.a-9.js",
-// expressions_delete_11_4_1_3_1_js,
-// "expressions/delete/11.4.1-3-1.js",
+success_expressions_delete_11_4_1_4_a_9_js,
+"expressions/delete/11.4.1-4.a-9.js",
+success_expressions_delete_11_4_1_3_1_js,
+"expressions/delete/11.4.1-3-1.js",
 // expressions_delete_11_4_1_5_1_js,
 // "expressions/delete/11.4.1-5-1.js",
 // expressions_delete_S8_12_7_A1_js,
 // "expressions/delete/S8.12.7_A1.js",
 // expressions_delete_S11_4_1_A3_3_T4_js,
 // "expressions/delete/S11.4.1_A3.3_T4.js",
-// expressions_delete_11_4_1_4_a_3_js,
-// "expressions/delete/11.4.1-4.a-3.js",
+success_expressions_delete_11_4_1_4_a_3_js,
+"expressions/delete/11.4.1-4.a-3.js",
 // expressions_delete_S11_4_1_A3_3_T1_js,
 // "expressions/delete/S11.4.1_A3.3_T1.js",
 // expressions_delete_white_space_line_terminator_between_delete_unaryexpression_allowed_js,
 // "expressions/delete/white-space-line-terminator-between-delete-unaryexpression-allowed.js",
 // expressions_delete_11_4_1_3_2_js,
 // "expressions/delete/11.4.1-3-2.js",
-// expressions_delete_11_4_1_4_a_5_js,
-// "expressions/delete/11.4.1-4.a-5.js",
-// expressions_delete_S8_12_7_A3_js,
-// "expressions/delete/S8.12.7_A3.js",
+success_expressions_delete_11_4_1_4_a_5_js,
+"expressions/delete/11.4.1-4.a-5.js",
+success_expressions_delete_S8_12_7_A3_js,
+"expressions/delete/S8.12.7_A3.js",
 // expressions_delete_11_4_1_0_1_js,
 // "expressions/delete/11.4.1-0-1.js",
 // expressions_delete_identifier_strict_js,
@@ -23954,8 +23958,8 @@ success_identifiers_part_unicode_11_0_0_js,
 // "expressions/delete/11.4.1-4.a-17.js",
 // expressions_delete_11_4_1_5_2_js,
 // "expressions/delete/11.4.1-5-2.js",
-// expressions_delete_11_4_1_2_2_js,
-// "expressions/delete/11.4.1-2-2.js",
+success_expressions_delete_11_4_1_2_2_js,
+"expressions/delete/11.4.1-2-2.js",
 // expressions_delete_11_4_1_5_a_27_s_js,
 // "expressions/delete/11.4.1-5-a-27-s.js",
 // expressions_delete_S11_4_1_A3_1_js,
@@ -31426,38 +31430,38 @@ success_expressions_array_11_1_4_0_js,
 // "expressions/array/spread-err-mult-err-itr-value.js",
 // expressions_array_S11_1_4_A1_5_js,
 // "expressions/array/S11.1.4_A1.5.js",
-// expressions_bitwise_not_S9_5_A2_1_T2_js,
-// "expressions/bitwise-not/S9.5_A2.1_T2.js",
-// expressions_bitwise_not_S11_4_8_A3_T5_js,
-// "expressions/bitwise-not/S11.4.8_A3_T5.js",
-// expressions_bitwise_not_S11_4_8_A3_T4_js,
-// "expressions/bitwise-not/S11.4.8_A3_T4.js",
-// expressions_bitwise_not_bigint_non_primitive_js,
-// "expressions/bitwise-not/bigint-non-primitive.js",
-// expressions_bitwise_not_S11_4_8_A2_1_T1_js,
-// "expressions/bitwise-not/S11.4.8_A2.1_T1.js",
-// expressions_bitwise_not_S11_4_8_A3_T2_js,
-// "expressions/bitwise-not/S11.4.8_A3_T2.js",
+success_expressions_bitwise_not_S9_5_A2_1_T2_js,
+"expressions/bitwise-not/S9.5_A2.1_T2.js",
+success_expressions_bitwise_not_S11_4_8_A3_T5_js,
+"expressions/bitwise-not/S11.4.8_A3_T5.js",
+success_expressions_bitwise_not_S11_4_8_A3_T4_js,
+"expressions/bitwise-not/S11.4.8_A3_T4.js",
+success_expressions_bitwise_not_bigint_non_primitive_js,
+"expressions/bitwise-not/bigint-non-primitive.js",
+success_expressions_bitwise_not_S11_4_8_A2_1_T1_js,
+"expressions/bitwise-not/S11.4.8_A2.1_T1.js",
+success_expressions_bitwise_not_S11_4_8_A3_T2_js,
+"expressions/bitwise-not/S11.4.8_A3_T2.js",
 // expressions_bitwise_not_S11_4_8_A1_js,
 // "expressions/bitwise-not/S11.4.8_A1.js",
-// expressions_bitwise_not_S11_4_8_A3_T3_js,
-// "expressions/bitwise-not/S11.4.8_A3_T3.js",
-// expressions_bitwise_not_S9_5_A3_1_T4_js,
-// "expressions/bitwise-not/S9.5_A3.1_T4.js",
-// expressions_bitwise_not_S11_4_8_A3_T1_js,
-// "expressions/bitwise-not/S11.4.8_A3_T1.js",
+success_expressions_bitwise_not_S11_4_8_A3_T3_js,
+"expressions/bitwise-not/S11.4.8_A3_T3.js",
+success_expressions_bitwise_not_S9_5_A3_1_T4_js,
+"expressions/bitwise-not/S9.5_A3.1_T4.js",
+success_expressions_bitwise_not_S11_4_8_A3_T1_js,
+"expressions/bitwise-not/S11.4.8_A3_T1.js",
 // expressions_bitwise_not_S11_4_8_A2_1_T2_js,
 // "expressions/bitwise-not/S11.4.8_A2.1_T2.js",
 // expressions_bitwise_not_S11_4_8_A2_2_T1_js,
 // "expressions/bitwise-not/S11.4.8_A2.2_T1.js",
-// expressions_bitwise_not_bigint_js,
-// "expressions/bitwise-not/bigint.js",
-// expressions_bitwise_not_S9_5_A3_2_T2_js,
-// "expressions/bitwise-not/S9.5_A3.2_T2.js",
-// expressions_bitwise_not_S9_5_A2_3_T2_js,
-// "expressions/bitwise-not/S9.5_A2.3_T2.js",
-// expressions_bitwise_not_S9_5_A2_2_T2_js,
-// "expressions/bitwise-not/S9.5_A2.2_T2.js",
+success_expressions_bitwise_not_bigint_js,
+"expressions/bitwise-not/bigint.js",
+success_expressions_bitwise_not_S9_5_A3_2_T2_js,
+"expressions/bitwise-not/S9.5_A3.2_T2.js",
+success_expressions_bitwise_not_S9_5_A2_3_T2_js,
+"expressions/bitwise-not/S9.5_A2.3_T2.js",
+success_expressions_bitwise_not_S9_5_A2_2_T2_js,
+"expressions/bitwise-not/S9.5_A2.2_T2.js",
 // expressions_compound_assignment_S11_13_2_A4_9_T2_2_js,
 // "expressions/compound-assignment/S11.13.2_A4.9_T2.2.js",
 // expressions_compound_assignment_S11_13_2_A4_9_T2_1_js,
@@ -32404,8 +32408,8 @@ success_expressions_typeof_get_value_ref_err_js,
 // "expressions/typeof/bigint.js",
 success_expressions_typeof_undefined_js,
 "expressions/typeof/undefined.js",
-// expressions_typeof_unresolvable_reference_js,
-// "expressions/typeof/unresolvable-reference.js",
+success_expressions_typeof_unresolvable_reference_js,
+"expressions/typeof/unresolvable-reference.js",
 success_expressions_typeof_boolean_js,
 "expressions/typeof/boolean.js",
 // expressions_typeof_get_value_js,
@@ -0,0 +1,283 @@
+use crate::environment::Environments;
+use crate::js::{self, ModuleResolver};
+use crate::test::eval::test_eval;
+
+use js_sys::Error;
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_test::*;
+
+/// Resolves specifiers from a fixed table.
+#[derive(Debug, Default)]
+struct MemoryResolver {
+  urls: HashMap<String, String>,
+}
+
+impl ModuleResolver for MemoryResolver {
+  fn resolve_bare(&self, specifier: &str) -> Result<String, Error> {
+    self
+      .urls
+      .get(specifier)
+      .cloned()
+      .ok_or_else(|| Error::new(&format!("ERROR: {:?} is not in the table.", specifier)))
+  }
+
+  fn resolve_bare_commonjs(&self, specifier: &str) -> Result<String, Error> {
+    self.resolve_bare(specifier)
+  }
+}
+
+#[wasm_bindgen_test]
+fn builtin_resolvers() {
+  assert_eq!(js::EsmSh.resolve("d3", None).unwrap(), "https://esm.sh/d3");
+  assert_eq!(
+    js::JsDelivr.resolve("d3@7", None).unwrap(),
+    "https://cdn.jsdelivr.net/npm/d3@7/+esm"
+  );
+  assert_eq!(
+    js::Unpkg.resolve("lodash-es", None).unwrap(),
+    "https://unpkg.com/lodash-es?module"
+  );
+  assert_eq!(
+    js::BaseUrl::new("https://mirror.example.com/npm")
+      .resolve("d3", None)
+      .unwrap(),
+    "https://mirror.example.com/npm/d3"
+  );
+  assert_eq!(
+    js::EsmSh
+      .resolve("https://cdn.jsdelivr.net/npm/d3/+esm", None)
+      .unwrap(),
+    "https://cdn.jsdelivr.net/npm/d3/+esm"
+  );
+  assert_eq!(
+    js::EsmSh
+      .resolve("../lib/plot.js", Some("https://example.com/notebooks/a/"))
+      .unwrap(),
+    "https://example.com/notebooks/lib/plot.js"
+  );
+  assert!(js::builtin_resolver("skypack", None).is_err());
+  assert!(js::builtin_resolver("base-url", None).is_err());
+}
+
+#[wasm_bindgen_test]
+async fn import_with_memory_resolver() {
+  let mut envs = Environments::new();
+  let mut resolver = MemoryResolver::default();
+  resolver.urls.insert(
+    String::from("answer"),
+    String::from("data:text/javascript,export default 42"),
+  );
+  envs.realm.borrow_mut().config.resolver = Rc::new(resolver);
+  let answer = test_eval("import answer from \"answer\"; answer", &mut envs)
+    .await
+    .unwrap();
+  assert_eq!(answer.borrow().as_ref().as_f64(), Some(42.0));
+  assert!(test_eval("import missing from \"missing\"", &mut envs)
+    .await
+    .is_err());
+}
+
+#[wasm_bindgen_test]
+fn import_map() {
+  let import_map = js::ImportMap::parse(
+    &JsValue::from_str(
+      r#"{
+        "imports": {"d3": "https://esm.sh/d3@7.8.5", "lodash/": "https://esm.sh/lodash-es@4/", "bad": 1},
+        "scopes": {"https://example.com/legacy/": {"d3": "https://esm.sh/d3@5"}}
+      }"#,
+    ),
+    Some("https://example.com/notebooks/"),
+  )
+  .unwrap();
+  assert_eq!(
+    import_map.resolve("d3", None).unwrap().unwrap(),
+    "https://esm.sh/d3@7.8.5"
+  );
+  assert_eq!(
+    import_map
+      .resolve("d3", Some("https://example.com/legacy/a.js"))
+      .unwrap()
+      .unwrap(),
+    "https://esm.sh/d3@5"
+  );
+  assert_eq!(
+    import_map.resolve("lodash/map.js", None).unwrap().unwrap(),
+    "https://esm.sh/lodash-es@4/map.js"
+  );
+  assert_eq!(import_map.resolve("react", None).unwrap(), None);
+  assert!(import_map.resolve("bad", None).is_err());
+  assert!(js::ImportMap::parse(&JsValue::from_str("[]"), None).is_err());
+}
+
+#[wasm_bindgen_test]
+async fn module_cache() {
+  let cache = js::ModuleCache::new(Rc::new(js::MemoryStore::default()));
+  let bundle = js_sys::Object::new();
+  js_sys::Reflect::set(
+    &bundle,
+    &JsValue::from_str("https://modules.example.com/a.js"),
+    &JsValue::from_str("import { b } from './b.js'; export default b * 2;"),
+  )
+  .unwrap();
+  js_sys::Reflect::set(
+    &bundle,
+    &JsValue::from_str("https://modules.example.com/b.js"),
+    &JsValue::from_str("export const b = 21;"),
+  )
+  .unwrap();
+  cache.seed(&bundle).await.unwrap();
+  let module = cache
+    .import("https://modules.example.com/a.js")
+    .await
+    .unwrap();
+  assert_eq!(
+    js_sys::Reflect::get(&module, &JsValue::from_str("default"))
+      .unwrap()
+      .as_f64(),
+    Some(42.0)
+  );
+  let exported = cache.export().await.unwrap();
+  assert_eq!(js_sys::Object::keys(&exported).length(), 2);
+}
+
+#[wasm_bindgen_test]
+async fn module_loader() {
+  let mut envs = Environments::new();
+  envs.realm.borrow_mut().config.loader = Some(js_sys::Function::new_with_args(
+    "url",
+    "return {
+      'file:///lib/a.js': 'import { b } from \"./b.js\"; export default b + 1;',
+      'file:///lib/b.js': 'export const b = 41;',
+    }[url];",
+  ));
+  let a = test_eval("import a from \"./lib/a.js\"; a", &mut envs)
+    .await
+    .unwrap();
+  assert_eq!(a.borrow().as_ref().as_f64(), Some(42.0));
+  assert!(test_eval("import c from \"./lib/c.js\"", &mut envs)
+    .await
+    .is_err());
+}
+
+#[wasm_bindgen_test]
+async fn concurrent_imports() {
+  let mut envs = Environments::new();
+  envs.realm.borrow_mut().config.loader = Some(js_sys::Function::new_with_args(
+    "url",
+    "globalThis.concurrentLoads = (globalThis.concurrentLoads || 0) + 1;
+    return {
+      'file:///a.js': 'import { b } from \"./b.js\"; export default b;',
+      'file:///b.js': 'export const b = 42;',
+      'file:///c.js': 'import d from \"./d.js\"; export default d;',
+      'file:///d.js': 'import c from \"./c.js\"; export default c;',
+    }[url];",
+  ));
+  let same = test_eval(
+    "const [x, y] = await Promise.all([import(\"./a.js\"), import(\"./a.js\")]); x === y ? x.default : 0",
+    &mut envs,
+  )
+  .await
+  .unwrap();
+  assert_eq!(same.borrow().as_ref().as_f64(), Some(42.0));
+  let loads = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("concurrentLoads")).unwrap();
+  assert_eq!(loads.as_f64(), Some(2.0));
+  let err = test_eval("import c from \"./c.js\"", &mut envs)
+    .await
+    .unwrap_err();
+  assert!(String::from(err.message()).contains("imported while it is evaluated"));
+}
+
+#[wasm_bindgen_test]
+async fn import_bindings() {
+  let mut envs = Environments::new();
+  envs.realm.borrow_mut().config.loader = Some(js_sys::Function::new_with_args(
+    "url",
+    "return {
+      'file:///a.js': 'export default 40; export const b = 2;',
+    }[url];",
+  ));
+  let sum = test_eval("import a, { b } from \"./a.js\"; a + b", &mut envs)
+    .await
+    .unwrap();
+  assert_eq!(sum.borrow().as_ref().as_f64(), Some(42.0));
+  let keys = test_eval("import * as ns from \"./a.js\"; Object.keys(ns).join()", &mut envs)
+    .await
+    .unwrap();
+  assert_eq!(keys.borrow().as_ref().as_string().as_deref(), Some("b,default"));
+  let err = test_eval("import { c } from \"./a.js\"", &mut envs)
+    .await
+    .unwrap_err();
+  assert_eq!(err.name(), "SyntaxError");
+  assert!(String::from(err.message()).contains("does not provide an export named \"c\""));
+}
+
+#[wasm_bindgen_test]
+async fn commonjs_modules() {
+  let mut envs = Environments::new();
+  envs.realm.borrow_mut().config.loader = Some(js_sys::Function::new_with_args(
+    "url",
+    "return {
+      'file:///a.js': 'const b = require(\"./b.js\"); module.exports = { sum: b.x + 1 };',
+      'file:///b.js': 'exports.x = 41;',
+      'file:///lib/c.js': 'require(\"./d.js\"); module.exports = [\"./d.js\"].map(require)[0].y;',
+      'file:///lib/d.js': 'exports.y = 2;',
+    }[url];",
+  ));
+  let sum = test_eval("require(\"./a.js\").sum", &mut envs)
+    .await
+    .unwrap();
+  assert_eq!(sum.borrow().as_ref().as_f64(), Some(42.0));
+  let sum = test_eval("import a from \"./a.js\"; a.sum", &mut envs)
+    .await
+    .unwrap();
+  assert_eq!(sum.borrow().as_ref().as_f64(), Some(42.0));
+  let answer = test_eval(
+    "import a from \"data:text/javascript,module.exports = { answer: 42 }\"; a.answer",
+    &mut envs,
+  )
+  .await
+  .unwrap();
+  assert_eq!(answer.borrow().as_ref().as_f64(), Some(42.0));
+  let y = test_eval("require(\"./lib/c.js\")", &mut envs)
+    .await
+    .unwrap();
+  assert_eq!(y.borrow().as_ref().as_f64(), Some(2.0));
+}
+
+#[wasm_bindgen_test]
+async fn cell_imports() {
+  let mut envs = Environments::new();
+  envs.cell = Some(String::from("a"));
+  test_eval(
+    "import { q } from \"data:text/javascript,export const q = 1, p = 2\"",
+    &mut envs,
+  )
+  .await
+  .unwrap();
+  let _ = test_eval("import \"cell:missing\"", &mut envs).await;
+  let realm = envs.realm.borrow();
+  let imports = &realm.imports["a"];
+  assert_eq!(imports.len(), 2);
+  assert_eq!(imports[0].exports, vec!["p", "q"]);
+  assert_eq!(imports[0].error, None);
+  assert_eq!(imports[1].url.as_deref(), Some("cell:missing"));
+  assert!(imports[1].error.is_some());
+}
+
+#[wasm_bindgen_test]
+async fn cell_exports_are_snapshots() {
+  let session = crate::Session::new();
+  let eval = |name: &str, input: &str| {
+    wasm_bindgen_futures::JsFuture::from(session.eval_named_cell(String::from(name), String::from(input)))
+  };
+  eval("a", "export let x = 1; export function inc() { x += 1; return x; }").await.unwrap();
+  let x = eval("b", "import { x, inc } from \"cell:a\"; inc() + x").await.unwrap();
+  assert_eq!(x.as_string().as_deref(), Some("3"));
+  let x = eval("c", "import { x } from \"cell:a\"; x").await.unwrap();
+  assert_eq!(x.as_string().as_deref(), Some("1"));
+  eval("a", "export let x = 5;").await.unwrap();
+  let x = eval("c", "import { x } from \"cell:a\"; x").await.unwrap();
+  assert_eq!(x.as_string().as_deref(), Some("5"));
+}
@@ -0,0 +1,133 @@
+use crate::builtin::deterministic::DeterministicMode;
+use crate::builtin::fetch::FetchPolicy;
+use crate::environment::{CapabilityPolicy, Environments, RcRealm};
+use crate::test::eval::test_eval;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_test::*;
+
+#[wasm_bindgen_test]
+fn default_policy() {
+  let policy = CapabilityPolicy::default();
+  assert!(policy.exposes("Math"));
+  assert!(policy.exposes("document.createElement"));
+  assert!(!policy.exposes("localStorage"));
+  assert!(!policy.exposes("document.querySelector"));
+}
+
+#[wasm_bindgen_test]
+fn policy_from_js() {
+  let options = js_sys::JSON::parse(
+    r#"{"allow": ["localStorage"], "deny": ["fetch"], "documentQueries": true, "wrappers": {"Math": 1}}"#,
+  )
+  .unwrap();
+  let policy = CapabilityPolicy::from_js(&options).unwrap();
+  assert!(policy.exposes("localStorage"));
+  assert!(!policy.exposes("fetch"));
+  assert!(policy.exposes("document.querySelector"));
+  let global = js_sys::global();
+  let proxy = policy.global_proxy(&global);
+  let get = |name: &str| js_sys::Reflect::get(&proxy, &JsValue::from_str(name)).unwrap();
+  assert!(get("fetch").is_undefined());
+  assert_eq!(get("Math").as_f64(), Some(1.0));
+  assert!(CapabilityPolicy::from_js(&JsValue::from_str("fetch")).is_err());
+}
+
+#[wasm_bindgen_test]
+async fn compiled_functions_use_policy() {
+  let mut policy = CapabilityPolicy::default();
+  policy.wrap("answer", JsValue::from_f64(42.0));
+  let mut envs = Environments::new();
+  envs.realm.borrow_mut().config.policy = policy.into();
+  envs.insert_globals().unwrap();
+  let result = test_eval("function f() { return answer; }; [1].map(f)[0]", &mut envs)
+    .await
+    .unwrap();
+  assert_eq!(result.borrow().as_ref().as_f64(), Some(42.0));
+}
+
+#[wasm_bindgen_test]
+fn fetch_policy() {
+  let policy = FetchPolicy::from_js(
+    &js_sys::JSON::parse(r#"{"origins": ["https://api.example.com/data"], "maxBytes": 10}"#).unwrap(),
+  )
+  .unwrap();
+  assert!(policy.allows("https://api.example.com/other?x=1"));
+  assert!(!policy.allows("https://example.com/"));
+  assert!(!policy.credentials);
+  assert_eq!(policy.max_bytes, 10.0);
+  assert!(FetchPolicy::default().allows("https://example.com/"));
+}
+
+#[wasm_bindgen_test]
+async fn fetch_body_limit() {
+  let mut envs = Environments::new();
+  envs.realm.borrow_mut().config.fetch = std::rc::Rc::new(FetchPolicy {
+    max_bytes: 4.0,
+    ..Default::default()
+  });
+  let text = test_eval("const response = await fetch('data:text/plain,abc'); await response.text()", &mut envs)
+    .await
+    .unwrap();
+  assert_eq!(text.borrow().as_ref().as_string().as_deref(), Some("abc"));
+  let err = test_eval("await fetch('data:text/plain,hello world')", &mut envs)
+    .await
+    .unwrap_err();
+  assert_eq!(err.name(), "RangeError");
+}
+
+async fn deterministic_run() -> String {
+  let realm = RcRealm::default();
+  realm.borrow_mut().config.deterministic = Some(DeterministicMode {
+    seed: String::from("42"),
+    start_time: 1000.0,
+  });
+  let mut envs = Environments::with_realm(realm);
+  let input = "JSON.stringify([Math.random(), crypto.getRandomValues(new Uint8Array(2)).join(), typeof crypto.subtle, performance.now(), Date.now()])";
+  let result = test_eval(input, &mut envs)
+    .await
+    .unwrap();
+  let result = result.borrow().as_ref().as_string().unwrap();
+  result
+}
+
+#[wasm_bindgen_test]
+async fn deterministic_mode() {
+  let first = deterministic_run().await;
+  assert_eq!(first, deterministic_run().await);
+  assert!(first.ends_with(",\"undefined\",0,1000]"));
+}
+
+#[wasm_bindgen_test]
+async fn fetch_redirects() {
+  let mut policy = CapabilityPolicy::default();
+  policy.wrap(
+    "fetch",
+    js_sys::Function::new_with_args(
+      "request",
+      "const redirects = {'https://api.example.com/a': 'https://evil.example.com/', 'https://api.example.com/b': '/c'};
+      if (request.redirect !== 'manual') {return new Response('followed');}
+      const location = redirects[request.url];
+      if (location) {return new Response(null, {status: 302, headers: {location}});}
+      return new Response(request.url);",
+    )
+    .into(),
+  );
+  let mut envs = Environments::new();
+  {
+    let mut realm = envs.realm.borrow_mut();
+    realm.config.policy = policy.into();
+    realm.config.fetch = std::rc::Rc::new(
+      FetchPolicy::from_js(&js_sys::JSON::parse(r#"{"origins": ["https://api.example.com"]}"#).unwrap()).unwrap(),
+    );
+  }
+  envs.insert_globals().unwrap();
+  let text = test_eval("await (await fetch('https://api.example.com/b')).text()", &mut envs)
+    .await
+    .unwrap();
+  assert_eq!(text.borrow().as_ref().as_string().as_deref(), Some("https://api.example.com/c"));
+  let err = test_eval("await fetch('https://api.example.com/a')", &mut envs)
+    .await
+    .unwrap_err();
+  assert!(String::from(err.message()).contains("https://evil.example.com/"));
+}
@@ -0,0 +1,298 @@
+use crate::Session;
+
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::*;
+
+#[wasm_bindgen_test]
+async fn sessions_are_independent() {
+  let first = Session::new();
+  let second = Session::new();
+  JsFuture::from(first.eval_cell(String::from("let x = 1;"))).await.unwrap();
+  JsFuture::from(second.eval_cell(String::from("let x = 2;"))).await.unwrap();
+  let x = JsFuture::from(first.eval_cell(String::from("x"))).await.unwrap();
+  assert_eq!(x.as_string().as_deref(), Some("1"));
+  assert_eq!(first.get_type("x").as_string().as_deref(), Some("number"));
+  second.reset_envs();
+  assert_eq!(second.get_type("x").as_string().as_deref(), Some("undefined"));
+  assert_eq!(first.get_type("x").as_string().as_deref(), Some("number"));
+}
+
+#[wasm_bindgen_test]
+async fn dependents_of_cells() {
+  let session = Session::new();
+  for (name, input) in [
+    ("a", "let x = 1;"),
+    ("b", "let y = x + 1;"),
+    ("c", "const z = [y].map((x) => x * 10)[0]; z"),
+    ("d", "function f(y) { let x = 2; return x + y; }"),
+  ] {
+    JsFuture::from(session.eval_named_cell(String::from(name), String::from(input))).await.unwrap();
+  }
+  let f = JsFuture::from(session.eval_cell(String::from("[1].map(f)[0]"))).await.unwrap();
+  assert_eq!(f.as_string().as_deref(), Some("3"));
+  let dependents = js_sys::Array::from(&session.get_dependents("a").unwrap());
+  assert_eq!(dependents.iter().map(|x| x.as_string().unwrap()).collect::<Vec<_>>(), ["b", "c"]);
+  JsFuture::from(session.eval_named_cell(String::from("a"), String::from("let x = 5;"))).await.unwrap();
+  let outputs = JsFuture::from(session.run_dependents(String::from("a"))).await.unwrap();
+  let last = js_sys::Array::from(&outputs).pop();
+  assert_eq!(js_sys::Reflect::get(&last, &"output".into()).unwrap().as_string().as_deref(), Some("60"));
+  JsFuture::from(session.eval_named_cell(String::from("a"), String::from("let x = z;"))).await.unwrap();
+  assert!(session.get_dependents("a").is_err());
+}
+
+#[wasm_bindgen_test]
+async fn cells_depend_on_imported_cells() {
+  let session = Session::new();
+  for (name, input) in [
+    ("a", "export const x = 1;"),
+    ("b", "import { x as y } from \"cell:a\"; y"),
+    ("c", "const b = import(\"cell:b\");"),
+  ] {
+    JsFuture::from(session.eval_named_cell(String::from(name), String::from(input))).await.unwrap();
+  }
+  let dependents = js_sys::Array::from(&session.get_dependents("a").unwrap());
+  assert_eq!(dependents.iter().map(|x| x.as_string().unwrap()).collect::<Vec<_>>(), ["b", "c"]);
+  let result = JsFuture::from(session.execute_cell(Some(String::from("a")), String::from("export const x = 2;")))
+    .await
+    .unwrap();
+  let stale = js_sys::Array::from(&js_sys::Reflect::get(&result, &"stale".into()).unwrap());
+  assert_eq!(stale.iter().map(|x| x.as_string().unwrap()).collect::<Vec<_>>(), ["b", "c"]);
+}
+
+#[wasm_bindgen_test]
+async fn cells_own_their_bindings() {
+  let session = Session::new();
+  JsFuture::from(session.eval_cell_with_id(String::from("a"), String::from("let x = 1; let html = 2;"))).await.unwrap();
+  JsFuture::from(session.eval_cell_with_id(String::from("a"), String::from("let y = 1;"))).await.unwrap();
+  assert_eq!(session.get_type("x").as_string().as_deref(), Some("undefined"));
+  assert_eq!(session.get_type("html").as_string().as_deref(), Some("function"));
+  JsFuture::from(session.delete_cell(String::from("a"))).await.unwrap();
+  assert_eq!(session.get_type("y").as_string().as_deref(), Some("undefined"));
+}
+
+#[wasm_bindgen_test]
+async fn tagged_templates() {
+  let session = Session::new();
+  let eval = |input: &str| session.eval_cell(String::from(input));
+  JsFuture::from(eval("function tag(strings, value) { return strings; } function get() { return tag`a${1}b`; }")).await.unwrap();
+  let same = JsFuture::from(eval("get() === get() && Object.isFrozen(get().raw)")).await.unwrap();
+  assert_eq!(same.as_string().as_deref(), Some("true"));
+  JsFuture::from(eval("let p = tag`x\\n${0}`;")).await.unwrap();
+  JsFuture::from(eval("let q = tag`x\\n${0}`;")).await.unwrap();
+  let other = JsFuture::from(eval("p === q || p.raw[0] !== 'x\\\\n'")).await.unwrap();
+  assert_eq!(other.as_string().as_deref(), Some("false"));
+  let raw = JsFuture::from(eval("String.raw`a\\n${1 + 1}b`")).await.unwrap();
+  assert_eq!(raw.as_string().as_deref(), Some("a\\n2b"));
+}
+
+#[wasm_bindgen_test]
+async fn structured_results() {
+  let session = Session::new();
+  let get = |object: &JsValue, key: &str| js_sys::Reflect::get(object, &key.into()).unwrap();
+  let result = JsFuture::from(session.execute_cell(None, String::from("console.log('a', 1); [1, 2]"))).await.unwrap();
+  assert_eq!(get(&result, "status").as_string().as_deref(), Some("ok"));
+  assert_eq!(get(&result, "type").as_string().as_deref(), Some("Array"));
+  assert_eq!(get(&get(&result, "data"), "text/plain").as_string().as_deref(), Some("(2) [1, 2]"));
+  let console = js_sys::Array::from(&get(&result, "console"));
+  assert_eq!(get(&console.get(0), "text").as_string().as_deref(), Some("a 1"));
+  let result = JsFuture::from(session.execute_cell(Some(String::from("b")), String::from("let x = 1;\nnull.y"))).await.unwrap();
+  assert_eq!(get(&result, "status").as_string().as_deref(), Some("error"));
+  let error = get(&result, "error");
+  assert_eq!(get(&error, "name").as_string().as_deref(), Some("TypeError"));
+  assert_eq!(get(&error, "line").as_f64(), Some(2.0));
+}
+
+#[wasm_bindgen_test]
+async fn mime_bundles() {
+  let session = Session::new();
+  let get = |object: &JsValue, key: &str| js_sys::Reflect::get(object, &key.into()).unwrap();
+  let result = JsFuture::from(session.execute_cell(None, String::from("new Map([['a', 1]])"))).await.unwrap();
+  assert_eq!(get(&get(&result, "data"), "text/plain").as_string().as_deref(), Some("Map(1) {\"a\" => 1}"));
+  let input = "[{ name: 'a', n: 1 }, { name: 'b', n: null, at: new Date(0) }]";
+  let result = JsFuture::from(session.execute_cell(None, String::from(input))).await.unwrap();
+  let resource = js_sys::JSON::stringify(&get(&get(&result, "data"), "application/vnd.dataresource+json")).unwrap();
+  assert_eq!(
+    String::from(resource),
+    concat!(
+      "{\"schema\":{\"fields\":[{\"name\":\"name\",\"type\":\"string\"},{\"name\":\"n\",\"type\":\"number\"},",
+      "{\"name\":\"at\",\"type\":\"datetime\"}]},\"data\":[{\"name\":\"a\",\"n\":1},",
+      "{\"name\":\"b\",\"n\":null,\"at\":\"1970-01-01T00:00:00.000Z\"}]}"
+    )
+  );
+  let result = JsFuture::from(session.execute_cell(None, String::from("new Array(1000000).fill(1)"))).await.unwrap();
+  let data = get(&result, "data");
+  assert!(get(&data, "application/json").is_undefined());
+  assert!(get(&data, "text/plain").as_string().unwrap().starts_with("(1000000) [1, 1,"));
+  let input = "({ [Symbol.for('display')]() { return { 'text/markdown': '# a' }; } })";
+  let result = JsFuture::from(session.execute_cell(None, String::from(input))).await.unwrap();
+  assert_eq!(get(&get(&result, "data"), "text/markdown").as_string().as_deref(), Some("# a"));
+  let input = "({ [Symbol.for('display')]() { return { 'a b': 1 }; } })";
+  let result = JsFuture::from(session.execute_cell(None, String::from(input))).await.unwrap();
+  assert_eq!(get(&result, "status").as_string().as_deref(), Some("error"));
+}
+
+#[wasm_bindgen_test]
+async fn record_tables() {
+  let session = Session::new();
+  let get = |object: &JsValue, key: &str| js_sys::Reflect::get(object, &key.into()).unwrap();
+  session.set_table_limits(js_sys::JSON::parse("{\"pageSize\": 2}").unwrap()).unwrap();
+  let input = "[{ name: 'a', n: 1 }, { name: 'bb', n: 10 }, { name: 'c', n: 100 }]";
+  let result = JsFuture::from(session.execute_cell(None, String::from(input))).await.unwrap();
+  let text = get(&get(&result, "data"), "text/plain").as_string().unwrap();
+  assert_eq!(text, "name   n\n----  --\na      1\nbb    10\n… 1 more row");
+  assert!(session.set_table_limits(js_sys::JSON::parse("{\"maxRows\": -1}").unwrap()).is_err());
+}
+
+#[wasm_bindgen_test]
+async fn inspect_values() {
+  let session = Session::new();
+  let get = |object: &JsValue, key: &str| js_sys::Reflect::get(object, &key.into()).unwrap();
+  let input = "class Point { constructor(x, y) { this.x = x; this.y = y; } }; ({ p: new Point(1, 2), m: new Map([['a', 1]]) })";
+  let result = JsFuture::from(session.execute_cell(Some(String::from("a")), String::from(input))).await.unwrap();
+  assert_eq!(get(&result, "preview").as_string().as_deref(), Some("{p: Point, m: Map(1)}"));
+  let handle = get(&result, "handle").as_f64().unwrap() as u32;
+  let path = js_sys::Array::of1(&"p".into());
+  let point = session.inspect(handle, JsValue::from(path)).unwrap();
+  assert_eq!(get(&point, "preview").as_string().as_deref(), Some("Point {x: 1, y: 2}"));
+  assert_eq!(js_sys::Array::from(&get(&point, "entries")).length(), 2);
+  JsFuture::from(session.delete_cell(String::from("a"))).await.unwrap();
+  assert!(session.inspect(handle, JsValue::NULL).is_err());
+}
+
+#[wasm_bindgen_test]
+async fn console_capture() {
+  let session = Session::new();
+  let get = |object: &JsValue, key: &str| js_sys::Reflect::get(object, &key.into()).unwrap();
+  let entries = js_sys::Array::new();
+  let handler = js_sys::Function::new_with_args("entries", "return (entry) => entries.push(entry);")
+    .call1(&JsValue::NULL, &entries).unwrap();
+  session.set_console_handler(Some(handler.into()));
+  let input = "console.group('g'); console.log('%d items', 2.5); console.groupEnd(); Promise.resolve().then(() => console.warn('late'));";
+  let result = JsFuture::from(session.execute_cell(Some(String::from("a")), String::from(input))).await.unwrap();
+  let console = js_sys::Array::from(&get(&result, "console"));
+  assert_eq!(get(&console.get(1), "text").as_string().as_deref(), Some("2 items"));
+  assert_eq!(get(&console.get(1), "depth").as_f64(), Some(1.0));
+  let result = JsFuture::from(session.execute_cell(Some(String::from("b")), String::from("1"))).await.unwrap();
+  assert_eq!(js_sys::Array::from(&get(&result, "console")).length(), 0);
+  let late = entries.find(&mut |entry, _, _| get(&entry, "text").as_string().as_deref() == Some("late"));
+  assert_eq!(get(&late, "cell").as_string().as_deref(), Some("a"));
+}
+
+#[wasm_bindgen_test]
+async fn display_outputs() {
+  let session = Session::new();
+  let get = |object: &JsValue, key: &str| js_sys::Reflect::get(object, &key.into()).unwrap();
+  let messages = js_sys::Array::new();
+  let handler = js_sys::Function::new_with_args("messages", "return (message) => messages.push(message);")
+    .call1(&JsValue::NULL, &messages).unwrap();
+  session.set_display_handler(Some(handler.into()));
+  let input = "display(1, { id: 'progress' }); display.update('progress', 'done'); display([1, 2]);";
+  JsFuture::from(session.execute_cell(Some(String::from("a")), String::from(input))).await.unwrap();
+  let timeout = js_sys::Function::new_no_args("return new Promise((resolve) => setTimeout(resolve, 0));");
+  JsFuture::from(js_sys::Promise::from(timeout.call0(&JsValue::NULL).unwrap())).await.unwrap();
+  assert_eq!(messages.length(), 3);
+  let update = messages.get(1);
+  assert_eq!(get(&update, "type").as_string().as_deref(), Some("update"));
+  assert_eq!(get(&update, "cell").as_string().as_deref(), Some("a"));
+  assert_eq!(get(&get(&update, "data"), "text/plain").as_string().as_deref(), Some("done"));
+  assert_eq!(get(&messages.get(2), "id").as_string().as_deref(), Some("display-1"));
+  let input = "const timer = setTimeout(() => display('late'), 0);";
+  JsFuture::from(session.execute_cell(Some(String::from("b")), String::from(input))).await.unwrap();
+  JsFuture::from(js_sys::Promise::from(timeout.call0(&JsValue::NULL).unwrap())).await.unwrap();
+  JsFuture::from(js_sys::Promise::from(timeout.call0(&JsValue::NULL).unwrap())).await.unwrap();
+  assert_eq!(messages.length(), 4);
+  assert_eq!(get(&messages.get(3), "cell").as_string().as_deref(), Some("b"));
+  let error = JsFuture::from(session.eval_cell(String::from("display.update('missing', 1)"))).await.unwrap();
+  assert!(error.as_string().unwrap().starts_with("ReferenceError"));
+}
+
+#[wasm_bindgen_test]
+async fn interrupt_cells() {
+  let session = Session::new();
+  let status = |result: &JsValue| js_sys::Reflect::get(result, &"status".into()).unwrap().as_string();
+  let running = JsFuture::from(session.execute_cell(Some(String::from("a")), String::from("let x = 1; while (true) {}")));
+  let queued = JsFuture::from(session.execute_cell(Some(String::from("b")), String::from("2")));
+  let timeout = js_sys::Function::new_no_args("return new Promise((resolve) => setTimeout(resolve, 200));");
+  JsFuture::from(js_sys::Promise::from(timeout.call0(&JsValue::NULL).unwrap())).await.unwrap();
+  session.interrupt();
+  assert_eq!(status(&running.await.unwrap()).as_deref(), Some("interrupted"));
+  assert_eq!(status(&queued.await.unwrap()).as_deref(), Some("interrupted"));
+  let cancelled = JsFuture::from(session.execute_cell(Some(String::from("c")), String::from("3")));
+  session.cancel_cell("c");
+  assert_eq!(status(&cancelled.await.unwrap()).as_deref(), Some("interrupted"));
+  let x = JsFuture::from(session.eval_cell(String::from("x + 1"))).await.unwrap();
+  assert_eq!(x.as_string().as_deref(), Some("2"));
+  let input = "await new Promise((resolve) => setTimeout(resolve, 200)); 4";
+  let running = JsFuture::from(session.execute_cell(Some(String::from("d")), String::from(input)));
+  let queued = session.execute_cell(Some(String::from("e")), String::from("5"));
+  session.cancel_cell("e");
+  let late = js_sys::Function::new_no_args("return new Promise((resolve) => setTimeout(() => resolve('late'), 50));");
+  let race = js_sys::Promise::race(&js_sys::Array::of2(&queued, &late.call0(&JsValue::NULL).unwrap()));
+  assert_eq!(status(&JsFuture::from(race).await.unwrap()).as_deref(), Some("interrupted"));
+  assert_eq!(status(&running.await.unwrap()).as_deref(), Some("ok"));
+  let output = JsFuture::from(session.eval_cell(String::from("6"))).await.unwrap();
+  assert_eq!(output.as_string().as_deref(), Some("6"));
+}
+
+#[wasm_bindgen_test]
+async fn configure_running_cells() {
+  let session = Session::new();
+  let entries = js_sys::Array::new();
+  let handler = js_sys::Function::new_with_args("entries", "return (entry) => entries.push(entry);")
+    .call1(&JsValue::NULL, &entries).unwrap();
+  let input = "await new Promise((resolve) => setTimeout(resolve, 50)); console.log('done'); 1";
+  let running = JsFuture::from(session.execute_cell(Some(String::from("a")), String::from(input)));
+  let timeout = js_sys::Function::new_no_args("return new Promise((resolve) => setTimeout(resolve, 0));");
+  JsFuture::from(js_sys::Promise::from(timeout.call0(&JsValue::NULL).unwrap())).await.unwrap();
+  session.set_console_handler(Some(handler.into()));
+  running.await.unwrap();
+  assert_eq!(entries.length(), 1);
+  let output = JsFuture::from(session.eval_cell(String::from("console.log('next'); 2"))).await.unwrap();
+  assert_eq!(output.as_string().as_deref(), Some("2"));
+  assert_eq!(entries.length(), 2);
+}
+
+#[wasm_bindgen_test]
+async fn policy_of_running_cells() {
+  let session = Session::new();
+  let input = "await new Promise((resolve) => setTimeout(resolve, 50)); typeof answer";
+  let running = JsFuture::from(session.eval_cell(String::from(input)));
+  let timeout = js_sys::Function::new_no_args("return new Promise((resolve) => setTimeout(resolve, 0));");
+  JsFuture::from(js_sys::Promise::from(timeout.call0(&JsValue::NULL).unwrap())).await.unwrap();
+  let policy = js_sys::JSON::parse(r#"{"wrappers": {"answer": 42}}"#).unwrap();
+  session.set_capability_policy(policy).unwrap();
+  let mode = js_sys::JSON::parse(r#"{"seed": 1, "startTime": 1000}"#).unwrap();
+  session.set_deterministic_mode(mode).unwrap();
+  assert_eq!(running.await.unwrap().as_string().as_deref(), Some("undefined"));
+  let output = JsFuture::from(session.eval_cell(String::from("answer + performance.now()"))).await.unwrap();
+  assert_eq!(output.as_string().as_deref(), Some("42"));
+}
+
+#[wasm_bindgen_test]
+async fn temporal_dead_zone() {
+  let session = Session::new();
+  let eval = |input: &str| JsFuture::from(session.eval_cell(String::from(input)));
+  assert_eq!(eval("typeof missing").await.unwrap().as_string().as_deref(), Some("undefined"));
+  let error = eval("typeof x; let x = 1;").await.unwrap().as_string().unwrap();
+  assert!(error.starts_with("ReferenceError"));
+  let error = eval("{ const y = y + 1; }").await.unwrap().as_string().unwrap();
+  assert!(error.starts_with("ReferenceError"));
+  assert_eq!(eval("let z = 2; typeof z").await.unwrap().as_string().as_deref(), Some("number"));
+}
+
+#[wasm_bindgen_test]
+async fn anonymous_results_are_released() {
+  let session = Session::new();
+  let mut handles = Vec::new();
+  for _ in 0..5 {
+    let result = JsFuture::from(session.execute_cell(None, String::from("display(1, { id: 'progress' }); [1, 2]")))
+      .await
+      .unwrap();
+    handles.push(js_sys::Reflect::get(&result, &"handle".into()).unwrap().as_f64().unwrap() as u32);
+  }
+  let live = handles.iter().filter(|x| session.inspect(**x, JsValue::NULL).is_ok()).count();
+  assert_eq!(live, 1);
+  let output = JsFuture::from(session.eval_cell(String::from("display.update('progress', 2)"))).await.unwrap();
+  assert!(output.as_string().unwrap().starts_with("ReferenceError"));
+}
@@ -76,6 +76,26 @@ impl Value {
     }
 }
 
+impl Value {
+    /// Converts the value to a string following the ECMAScript ToString operation.
+    pub fn to_js_string(&self) -> Result<JsString, Error> {
+        match self {
+            Value::String(x) => Ok(x.clone()),
+            Value::Symbol(_) => Err(Error::from(js_sys::TypeError::new(
+                "TypeError: Cannot convert a Symbol value to a string.",
+            ))),
+            x => {
+                let string = js_sys::Function::from(js_sys::Reflect::get(
+                    &js_sys::global(),
+                    &JsValue::from_str("String"),
+                )?)
+                .call1(&JsValue::undefined(), x.as_ref())?;
+                Ok(JsString::from(string))
+            }
+        }
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {